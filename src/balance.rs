@@ -0,0 +1,267 @@
+use crate::config::{LoadBalance, ProviderConfig};
+use std::collections::HashMap;
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::sync::{Mutex, PoisonError};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Default)]
+struct State {
+    next: usize,
+    current: Vec<i64>,
+}
+
+pub(crate) struct LoadBalancer {
+    states: Mutex<HashMap<String, State>>,
+    rng: Mutex<u64>,
+}
+
+impl Default for LoadBalancer {
+    fn default() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos() as u64)
+            .unwrap_or_default();
+        Self::seeded(RandomState::new().hash_one(nanos))
+    }
+}
+
+impl LoadBalancer {
+    pub(crate) fn seeded(seed: u64) -> Self {
+        Self {
+            states: Mutex::new(HashMap::new()),
+            rng: Mutex::new(seed.max(1)),
+        }
+    }
+
+    pub(crate) fn select(&self, name: &str, provider: &ProviderConfig) -> Option<usize> {
+        let replicas = provider.base_urls.len();
+        if replicas == 0 {
+            return None;
+        }
+
+        let weights: Vec<u64> = provider
+            .base_urls
+            .iter()
+            .map(|replica| u64::from(replica.weight()))
+            .collect();
+
+        match provider.load_balance.unwrap_or_default() {
+            LoadBalance::RoundRobin => {
+                let mut states = self.states.lock().unwrap_or_else(PoisonError::into_inner);
+                let state = states.entry(name.to_string()).or_default();
+                let index = state.next % replicas;
+                state.next = index + 1;
+                Some(index)
+            }
+            LoadBalance::Weighted => {
+                let mut states = self.states.lock().unwrap_or_else(PoisonError::into_inner);
+                let state = states.entry(name.to_string()).or_default();
+                Some(smooth_weighted(&mut state.current, &weights))
+            }
+            LoadBalance::Random => {
+                let total: u64 = weights.iter().sum();
+                if total == 0 {
+                    return Some(self.random_below(replicas as u64) as usize);
+                }
+
+                let mut point = self.random_below(total);
+                weights.iter().position(|&weight| {
+                    if point < weight {
+                        return true;
+                    }
+                    point -= weight;
+                    false
+                })
+            }
+        }
+    }
+
+    fn random_below(&self, bound: u64) -> u64 {
+        let zone = u64::MAX - u64::MAX % bound;
+        let mut state = self.rng.lock().unwrap_or_else(PoisonError::into_inner);
+        loop {
+            let value = xorshift(&mut state);
+            if value < zone {
+                return value % bound;
+            }
+        }
+    }
+}
+
+fn xorshift(state: &mut u64) -> u64 {
+    let mut x = *state;
+    x ^= x << 13;
+    x ^= x >> 7;
+    x ^= x << 17;
+    *state = x;
+    x
+}
+
+fn smooth_weighted(current: &mut Vec<i64>, weights: &[u64]) -> usize {
+    if current.len() != weights.len() {
+        *current = vec![0; weights.len()];
+    }
+
+    let total: i64 = weights.iter().map(|&weight| weight as i64).sum();
+    for (current, &weight) in current.iter_mut().zip(weights) {
+        *current += weight as i64;
+    }
+
+    let mut best = 0;
+    for (index, &value) in current.iter().enumerate() {
+        if value > current[best] {
+            best = index;
+        }
+    }
+    current[best] -= total;
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CallixBuilder;
+    use crate::config::{EndpointConfig, ReplicaUrl};
+    use crate::test_support::{MockServer, Reply};
+    use std::time::Duration;
+
+    fn provider(strategy: LoadBalance, weights: &[u32]) -> ProviderConfig {
+        ProviderConfig {
+            base_urls: weights
+                .iter()
+                .enumerate()
+                .map(|(index, &weight)| ReplicaUrl::Weighted {
+                    url: format!("https://replica{}.test", index),
+                    weight,
+                })
+                .collect(),
+            load_balance: Some(strategy),
+            ..ProviderConfig::default()
+        }
+    }
+
+    fn counts(balancer: &LoadBalancer, provider: &ProviderConfig, picks: usize) -> Vec<usize> {
+        let mut counts = vec![0; provider.base_urls.len()];
+        for _ in 0..picks {
+            counts[balancer.select("p", provider).unwrap()] += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn no_replicas_selects_nothing() {
+        let balancer = LoadBalancer::seeded(1);
+        assert_eq!(balancer.select("p", &ProviderConfig::default()), None);
+    }
+
+    #[test]
+    fn round_robin_cycles_through_replicas() {
+        let balancer = LoadBalancer::seeded(1);
+        let provider = provider(LoadBalance::RoundRobin, &[1, 1, 1]);
+
+        let picks: Vec<_> = (0..7)
+            .map(|_| balancer.select("p", &provider).unwrap())
+            .collect();
+        assert_eq!(picks, [0, 1, 2, 0, 1, 2, 0]);
+    }
+
+    #[test]
+    fn round_robin_state_is_per_provider() {
+        let balancer = LoadBalancer::seeded(1);
+        let provider = provider(LoadBalance::RoundRobin, &[1, 1]);
+
+        assert_eq!(balancer.select("a", &provider), Some(0));
+        assert_eq!(balancer.select("b", &provider), Some(0));
+        assert_eq!(balancer.select("a", &provider), Some(1));
+    }
+
+    #[test]
+    fn weighted_interleaves_by_weight() {
+        let balancer = LoadBalancer::seeded(1);
+        let provider = provider(LoadBalance::Weighted, &[3, 1]);
+
+        let picks: Vec<_> = (0..8)
+            .map(|_| balancer.select("p", &provider).unwrap())
+            .collect();
+        assert_eq!(picks, [0, 0, 1, 0, 0, 0, 1, 0]);
+    }
+
+    #[test]
+    fn weighted_matches_weight_ratio() {
+        let balancer = LoadBalancer::seeded(1);
+        let provider = provider(LoadBalance::Weighted, &[5, 3, 2]);
+
+        assert_eq!(counts(&balancer, &provider, 1000), [500, 300, 200]);
+    }
+
+    #[test]
+    fn weighted_skips_zero_weight_replicas() {
+        let balancer = LoadBalancer::seeded(1);
+        let provider = provider(LoadBalance::Weighted, &[0, 1]);
+
+        assert_eq!(counts(&balancer, &provider, 10), [0, 10]);
+    }
+
+    #[test]
+    fn random_is_roughly_uniform() {
+        let balancer = LoadBalancer::seeded(42);
+        let provider = provider(LoadBalance::Random, &[1, 1, 1, 1]);
+
+        for count in counts(&balancer, &provider, 40_000) {
+            assert!((9_000..11_000).contains(&count), "count {}", count);
+        }
+    }
+
+    #[test]
+    fn random_follows_weights() {
+        let balancer = LoadBalancer::seeded(7);
+        let provider = provider(LoadBalance::Random, &[3, 1]);
+
+        let counts = counts(&balancer, &provider, 40_000);
+        assert!((29_000..31_000).contains(&counts[0]), "counts {:?}", counts);
+        assert!((9_000..11_000).contains(&counts[1]), "counts {:?}", counts);
+    }
+
+    #[test]
+    fn random_is_deterministic_for_a_seed() {
+        let provider = provider(LoadBalance::Random, &[1, 1, 1]);
+        let first = LoadBalancer::seeded(9);
+        let second = LoadBalancer::seeded(9);
+
+        for _ in 0..20 {
+            assert_eq!(first.select("p", &provider), second.select("p", &provider));
+        }
+    }
+
+    #[tokio::test]
+    async fn requests_rotate_across_replicas_via_the_client() {
+        let first = MockServer::start(vec![Reply::ok("first")]).await;
+        let second = MockServer::start(vec![Reply::ok("second")]).await;
+        let replicas = ProviderConfig {
+            base_urls: vec![
+                ReplicaUrl::Url(first.url.clone()),
+                ReplicaUrl::Url(second.url.clone()),
+            ],
+            load_balance: Some(LoadBalance::RoundRobin),
+            ..ProviderConfig::default()
+        }
+        .endpoint("call", EndpointConfig::new("GET", "/"));
+        let mut callix = CallixBuilder::new().build().unwrap();
+        callix.register_provider("pool", replicas).unwrap();
+        let derived = callix.with_timeout(Duration::from_secs(5));
+
+        let mut bodies = Vec::new();
+        for client in [&callix, &derived, &callix, &derived] {
+            let response = client
+                .request("pool", "call")
+                .unwrap()
+                .send()
+                .await
+                .unwrap();
+            bodies.push(response.text().await.unwrap());
+        }
+        assert_eq!(bodies, ["first", "second", "first", "second"]);
+        assert_eq!((first.hits(), second.hits()), (2, 2));
+    }
+}
@@ -0,0 +1,85 @@
+use crate::error::{CallixError, Result};
+use crate::response::CallixResponse;
+use serde::de::DeserializeOwned;
+
+pub struct BatchResult {
+    results: Vec<Result<CallixResponse>>,
+}
+
+impl BatchResult {
+    #[inline]
+    pub fn new(results: Vec<Result<CallixResponse>>) -> Self {
+        Self { results }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.results.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.results.is_empty()
+    }
+
+    #[inline]
+    pub fn all_ok(&self) -> bool {
+        self.results.iter().all(Result::is_ok)
+    }
+
+    pub fn successes(&self) -> Vec<&CallixResponse> {
+        self.results
+            .iter()
+            .filter_map(|r| r.as_ref().ok())
+            .collect()
+    }
+
+    pub fn failures(&self) -> Vec<&CallixError> {
+        self.results
+            .iter()
+            .filter_map(|r| r.as_ref().err())
+            .collect()
+    }
+
+    pub async fn collect_json<T: DeserializeOwned>(self) -> Result<Vec<T>> {
+        let mut bodies = Vec::new();
+        for response in self.results.into_iter().flatten() {
+            bodies.push(response.json().await?);
+        }
+        Ok(bodies)
+    }
+
+    #[inline]
+    pub fn into_results(self) -> Vec<Result<CallixResponse>> {
+        self.results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::CallixBuilder;
+    use crate::error::CallixError;
+    use crate::test_support::{MockServer, Reply, endpoint, mock_client};
+    use serde_json::Value;
+
+    #[tokio::test]
+    async fn partitions_successes_and_failures() {
+        let server = MockServer::start(vec![Reply::json(200, r#"{"ok":true}"#)]).await;
+        let callix = mock_client(CallixBuilder::new(), &server, endpoint("GET", "/{{id}}"));
+
+        let requests = vec![
+            callix.request("mock", "call").unwrap().var("id", "a"),
+            callix.request("mock", "call").unwrap(),
+            callix.request("mock", "call").unwrap().var("id", "b"),
+        ];
+        let batch = callix.batch(requests).await;
+
+        assert_eq!(batch.len(), 3);
+        assert!(!batch.all_ok());
+        assert_eq!(batch.successes().len(), 2);
+        assert!(matches!(batch.failures()[..], [CallixError::TemplateError]));
+
+        let bodies: Vec<Value> = batch.collect_json().await.unwrap();
+        assert_eq!(bodies, vec![serde_json::json!({"ok": true}); 2]);
+    }
+}
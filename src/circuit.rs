@@ -0,0 +1,186 @@
+use crate::error::{CallixError, Result};
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    HalfOpen { since: Instant },
+}
+
+pub(crate) struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    states: Mutex<HashMap<String, State>>,
+}
+
+impl CircuitBreaker {
+    pub(crate) fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            threshold: threshold.max(1),
+            cooldown,
+            states: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) fn allow(&self, provider: &str) -> Result<()> {
+        let mut states = self.states.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(state) = states.get_mut(provider) else {
+            return Ok(());
+        };
+
+        let now = Instant::now();
+        match *state {
+            State::Closed { .. } => Ok(()),
+            State::Open { until } if now < until => {
+                Err(CallixError::CircuitOpen(provider.to_string()))
+            }
+            State::HalfOpen { since } if now < since + self.cooldown => {
+                Err(CallixError::CircuitOpen(provider.to_string()))
+            }
+            State::Open { .. } | State::HalfOpen { .. } => {
+                *state = State::HalfOpen { since: now };
+                Ok(())
+            }
+        }
+    }
+
+    pub(crate) fn is_open(&self, provider: &str) -> bool {
+        let states = self.states.lock().unwrap_or_else(PoisonError::into_inner);
+        let now = Instant::now();
+        match states.get(provider) {
+            Some(State::Open { until }) => now < *until,
+            Some(State::HalfOpen { since }) => now < *since + self.cooldown,
+            Some(State::Closed { .. }) | None => false,
+        }
+    }
+
+    pub(crate) fn record(&self, provider: &str, success: bool) {
+        let mut states = self.states.lock().unwrap_or_else(PoisonError::into_inner);
+        if success {
+            states.remove(provider);
+            return;
+        }
+
+        let state = states
+            .entry(provider.to_string())
+            .or_insert(State::Closed { failures: 0 });
+        let failures = match *state {
+            State::Closed { failures } => failures + 1,
+            State::Open { .. } | State::HalfOpen { .. } => self.threshold,
+        };
+
+        *state = if failures >= self.threshold {
+            State::Open {
+                until: Instant::now() + self.cooldown,
+            }
+        } else {
+            State::Closed { failures }
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CallixBuilder;
+    use crate::config::EndpointConfig;
+    use crate::test_support::{MockServer, Reply, mock_client};
+
+    const COOLDOWN: Duration = Duration::from_millis(50);
+
+    fn tripped(threshold: u32) -> CircuitBreaker {
+        let circuit = CircuitBreaker::new(threshold, COOLDOWN);
+        for _ in 0..threshold {
+            circuit.record("api", false);
+        }
+        circuit
+    }
+
+    #[test]
+    fn opens_after_consecutive_failures() {
+        let circuit = CircuitBreaker::new(3, COOLDOWN);
+        circuit.record("api", false);
+        circuit.record("api", false);
+        assert!(circuit.allow("api").is_ok());
+
+        circuit.record("api", false);
+        assert!(circuit.is_open("api"));
+        match circuit.allow("api") {
+            Err(CallixError::CircuitOpen(provider)) => assert_eq!(provider, "api"),
+            other => panic!("expected an open circuit, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn a_success_resets_the_failure_count() {
+        let circuit = CircuitBreaker::new(2, COOLDOWN);
+        circuit.record("api", false);
+        circuit.record("api", true);
+        circuit.record("api", false);
+
+        assert!(!circuit.is_open("api"));
+        assert!(circuit.allow("api").is_ok());
+    }
+
+    #[test]
+    fn circuits_are_tracked_per_provider() {
+        let circuit = tripped(1);
+
+        assert!(circuit.allow("api").is_err());
+        assert!(circuit.allow("other").is_ok());
+        assert!(!circuit.is_open("other"));
+    }
+
+    #[test]
+    fn half_open_admits_a_single_trial_after_the_cooldown() {
+        let circuit = tripped(2);
+        std::thread::sleep(COOLDOWN * 2);
+
+        assert!(!circuit.is_open("api"));
+        assert!(circuit.allow("api").is_ok());
+        assert!(circuit.is_open("api"));
+        assert!(circuit.allow("api").is_err());
+    }
+
+    #[test]
+    fn a_successful_trial_closes_the_circuit() {
+        let circuit = tripped(2);
+        std::thread::sleep(COOLDOWN * 2);
+        circuit.allow("api").unwrap();
+
+        circuit.record("api", true);
+        assert!(circuit.allow("api").is_ok());
+        assert!(circuit.allow("api").is_ok());
+    }
+
+    #[test]
+    fn a_failed_trial_reopens_the_circuit() {
+        let circuit = tripped(2);
+        std::thread::sleep(COOLDOWN * 2);
+        circuit.allow("api").unwrap();
+
+        circuit.record("api", false);
+        assert!(circuit.is_open("api"));
+        assert!(circuit.allow("api").is_err());
+    }
+
+    #[tokio::test]
+    async fn an_open_circuit_fails_fast_without_sending() {
+        let server = MockServer::start(vec![Reply::new(500, &[], "down")]).await;
+        let builder = CallixBuilder::new()
+            .retries(0)
+            .circuit_breaker(2, Duration::from_secs(60));
+        let callix = mock_client(builder, &server, EndpointConfig::new("GET", "/"));
+
+        for _ in 0..2 {
+            let response = callix.request("mock", "call").unwrap().send().await;
+            assert_eq!(response.unwrap().status(), 500);
+        }
+        let result = callix.request("mock", "call").unwrap().send().await;
+        assert!(matches!(result, Err(CallixError::CircuitOpen(_))));
+        assert_eq!(server.hits(), 2);
+    }
+}
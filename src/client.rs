@@ -1,14 +1,48 @@
-use crate::config::Config;
+use crate::CallixBuilder;
+use crate::balance::LoadBalancer;
+use crate::batch::BatchResult;
+use crate::circuit::CircuitBreaker;
+use crate::config::{Config, EndpointConfig, ProviderConfig};
 use crate::error::{CallixError, Result};
+use crate::health::HealthStatus;
+use crate::middleware::Middleware;
 use crate::request::RequestBuilder;
+use crate::retry::RetryPolicy;
+use crate::sequence::Step;
+use crate::template::TemplateEngine;
+use crate::token_file::TokenFiles;
+use futures_util::future::join_all;
 use reqwest::{Client, Method};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, PoisonError, RwLock};
 use std::time::Duration;
 
 pub struct Callix {
-    config: Config,
+    config: Arc<RwLock<Arc<Config>>>,
+    config_path: Option<String>,
+    strict_config: bool,
+    validate_config: bool,
+    https_only: bool,
     client: Client,
-    max_retries: u32,
-    retry_delay: Duration,
+    retry: RetryPolicy,
+    check_url_expiry: bool,
+    middlewares: Vec<Arc<dyn Middleware>>,
+    sort_keys: bool,
+    request_id_header: Option<String>,
+    max_response_size: Option<usize>,
+    accept_encoding: Option<String>,
+    default_headers: Vec<(String, String)>,
+    success_statuses: Option<Vec<u16>>,
+    circuit: Option<Arc<CircuitBreaker>>,
+    balancer: Arc<LoadBalancer>,
+    hedge: Option<Duration>,
+    timeout: Option<Duration>,
+    token_files: Arc<TokenFiles>,
+    #[cfg(feature = "tracing")]
+    trace_fields: String,
+    global_vars: Arc<RwLock<HashMap<String, Value>>>,
 }
 
 impl Callix {
@@ -18,36 +52,439 @@ impl Callix {
         max_retries: u32,
         retry_delay: Duration,
     ) -> Result<Self> {
-        let config = match config_path {
-            Some(path) => Config::from_file(&path)?,
-            None => Config::default_config(),
+        Self::from_builder(CallixBuilder {
+            config_path,
+            timeout,
+            retry: RetryPolicy::new(max_retries, retry_delay),
+            ..CallixBuilder::default()
+        })
+    }
+
+    pub fn with_client(
+        client: Client,
+        config: Config,
+        max_retries: u32,
+        retry_delay: Duration,
+    ) -> Self {
+        let builder = CallixBuilder {
+            retry: RetryPolicy::new(max_retries, retry_delay),
+            ..CallixBuilder::default()
         };
+        Self::assemble(builder, config, client)
+    }
+
+    pub(crate) fn from_builder(mut builder: CallixBuilder) -> Result<Self> {
+        if builder.require_config && builder.config_path.is_none() {
+            return Err(CallixError::ConfigNotFound);
+        }
 
-        let client = Client::builder().timeout(timeout).build()?;
+        let config = load_config(
+            builder.config_path.as_deref(),
+            builder.strict_config,
+            builder.validate_on_build,
+            builder.https_only,
+        )?;
 
-        Ok(Self {
-            config,
+        let client = match builder.client.take() {
+            Some(client) => client,
+            None => {
+                let client = Client::builder()
+                    .timeout(builder.timeout)
+                    .local_address(builder.local_address)
+                    .https_only(builder.https_only);
+                #[cfg(feature = "socks")]
+                let client = match &builder.socks5_proxy {
+                    Some(url) => client.proxy(socks5_proxy(url)?),
+                    None => client,
+                };
+                client.build()?
+            }
+        };
+
+        Ok(Self::assemble(builder, config, client))
+    }
+
+    fn assemble(builder: CallixBuilder, mut config: Config, client: Client) -> Self {
+        config.prepare();
+
+        Self {
+            config: Arc::new(RwLock::new(Arc::new(config))),
+            config_path: builder.config_path,
+            strict_config: builder.strict_config,
+            validate_config: builder.validate_on_build,
+            https_only: builder.https_only,
             client,
-            max_retries,
-            retry_delay,
+            retry: builder.retry,
+            check_url_expiry: builder.check_url_expiry,
+            middlewares: builder.middlewares,
+            sort_keys: builder.sort_keys,
+            request_id_header: builder.request_id_header,
+            max_response_size: builder.max_response_size,
+            accept_encoding: builder.accept_encoding,
+            default_headers: builder.default_headers,
+            success_statuses: builder.success_statuses,
+            circuit: builder
+                .circuit_breaker
+                .map(|(failures, cooldown)| Arc::new(CircuitBreaker::new(failures, cooldown))),
+            balancer: Arc::default(),
+            hedge: builder.hedge,
+            timeout: None,
+            token_files: Arc::default(),
+            #[cfg(feature = "tracing")]
+            trace_fields: format_trace_fields(&builder.trace_fields),
+            global_vars: Arc::default(),
+        }
+    }
+
+    pub fn with_timeout(&self, timeout: Duration) -> Self {
+        Self {
+            config: Arc::clone(&self.config),
+            config_path: self.config_path.clone(),
+            strict_config: self.strict_config,
+            validate_config: self.validate_config,
+            https_only: self.https_only,
+            client: self.client.clone(),
+            retry: self.retry.clone(),
+            check_url_expiry: self.check_url_expiry,
+            middlewares: self.middlewares.clone(),
+            sort_keys: self.sort_keys,
+            request_id_header: self.request_id_header.clone(),
+            max_response_size: self.max_response_size,
+            accept_encoding: self.accept_encoding.clone(),
+            default_headers: self.default_headers.clone(),
+            success_statuses: self.success_statuses.clone(),
+            circuit: self.circuit.clone(),
+            balancer: Arc::clone(&self.balancer),
+            hedge: self.hedge,
+            timeout: Some(timeout),
+            token_files: Arc::clone(&self.token_files),
+            #[cfg(feature = "tracing")]
+            trace_fields: self.trace_fields.clone(),
+            global_vars: Arc::clone(&self.global_vars),
+        }
+    }
+
+    pub fn request(&self, provider: &str, endpoint: &str) -> Result<RequestBuilder<'_>> {
+        let config = self.config();
+        let global_vars = self
+            .global_vars
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+
+        let request =
+            RequestBuilder::shared(&self.client, Arc::clone(&config), provider, endpoint)?;
+        let replica = self
+            .balancer
+            .select(provider, config.get_provider(provider)?);
+
+        let request = request
+            .replica(replica)
+            .circuit(self.circuit.as_deref())
+            .timeout_override(self.timeout)
+            .token_files(&self.token_files)
+            .check_url_expiry(self.check_url_expiry)
+            .retry_policy(self.retry.clone())
+            .sort_keys(self.sort_keys)
+            .request_id_header(self.request_id_header.clone())
+            .max_response_size(self.max_response_size)
+            .accept_encoding(self.accept_encoding.clone())
+            .default_headers(&self.default_headers)
+            .success_statuses(self.success_statuses.clone())
+            .middlewares(&self.middlewares)
+            .vars(global_vars);
+        let request = match self.hedge {
+            Some(delay) => request.hedge(delay),
+            None => request,
+        };
+        #[cfg(feature = "tracing")]
+        let request = request.trace_fields(&self.trace_fields);
+
+        Ok(request)
+    }
+
+    pub fn reload_config(&self) -> Result<()> {
+        let path = self
+            .config_path
+            .as_deref()
+            .ok_or(CallixError::ConfigNotFound)?;
+        let config = load_config(
+            Some(path),
+            self.strict_config,
+            self.validate_config,
+            self.https_only,
+        )?;
+
+        *self.config.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(config);
+        Ok(())
+    }
+
+    pub fn config(&self) -> Arc<Config> {
+        self.config
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    fn update_config<R>(&mut self, update: impl FnOnce(&mut Config) -> R) -> R {
+        let mut config = self.config.write().unwrap_or_else(PoisonError::into_inner);
+        update(Arc::make_mut(&mut config))
+    }
+
+    pub fn set_global_var<T: Serialize>(&self, key: impl Into<String>, value: T) {
+        if let Ok(json_value) = serde_json::to_value(value) {
+            self.global_vars
+                .write()
+                .unwrap_or_else(PoisonError::into_inner)
+                .insert(key.into(), json_value);
+        }
+    }
+
+    pub fn remove_global_var(&self, key: &str) -> Option<Value> {
+        self.global_vars
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(key)
+    }
+
+    pub fn register_provider(
+        &mut self,
+        name: impl Into<String>,
+        mut provider: ProviderConfig,
+    ) -> Result<()> {
+        let name = name.into();
+        provider.validate(&name)?;
+        if self.https_only {
+            provider.require_https(&name)?;
+        }
+
+        provider.prepare();
+        self.update_config(|config| config.providers.insert(name, provider));
+        Ok(())
+    }
+
+    pub fn register_endpoint(
+        &mut self,
+        provider: &str,
+        name: impl Into<String>,
+        mut endpoint: EndpointConfig,
+    ) -> Result<()> {
+        endpoint.prepare();
+        self.update_config(|config| {
+            config
+                .providers
+                .get_mut(provider)
+                .ok_or(CallixError::ProviderNotFound)?
+                .endpoints
+                .insert(name.into(), endpoint);
+            Ok(())
         })
     }
 
-    pub fn request(&self, provider: &str, endpoint: &str) -> Result<RequestBuilder> {
-        let provider_config = self.config.get_provider(provider)?;
-        let endpoint_config = provider_config
-            .endpoints
-            .get(endpoint)
-            .ok_or_else(|| CallixError::EndpointNotFound(endpoint.to_string()))?;
+    pub async fn batch(&self, requests: Vec<RequestBuilder<'_>>) -> BatchResult {
+        let results = join_all(requests.into_iter().map(RequestBuilder::send)).await;
+        BatchResult::new(results)
+    }
+
+    pub async fn status(&self) -> HashMap<String, HealthStatus> {
+        let config = self.config();
+        let global_vars = self
+            .global_vars
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+
+        let mut report = HashMap::new();
+        let mut checks = Vec::new();
+        for (name, provider) in &config.providers {
+            let Some(path) = &provider.health_path else {
+                continue;
+            };
+            let targets: Vec<_> = if provider.base_urls.is_empty() {
+                vec![(name.clone(), None)]
+            } else {
+                provider
+                    .base_urls
+                    .iter()
+                    .enumerate()
+                    .map(|(index, replica)| (format!("{}@{}", name, replica.url()), Some(index)))
+                    .collect()
+            };
+
+            if self
+                .circuit
+                .as_ref()
+                .is_some_and(|circuit| circuit.is_open(name))
+            {
+                report.extend(
+                    targets
+                        .into_iter()
+                        .map(|(key, _)| (key, HealthStatus::CircuitOpen)),
+                );
+                continue;
+            }
+            let endpoint = EndpointConfig::new("GET", path.clone());
+            checks.extend(
+                targets
+                    .into_iter()
+                    .map(|(key, replica)| (key, provider, endpoint.clone(), replica)),
+            );
+        }
+
+        let pings = checks.iter().map(|(key, provider, endpoint, replica)| {
+            let request = RequestBuilder::new(&self.client, provider, endpoint, 0, Duration::ZERO)
+                .replica(*replica)
+                .timeout_override(self.timeout)
+                .token_files(&self.token_files)
+                .check_url_expiry(self.check_url_expiry)
+                .request_id_header(self.request_id_header.clone())
+                .accept_encoding(self.accept_encoding.clone())
+                .default_headers(&self.default_headers)
+                .middlewares(&self.middlewares)
+                .vars(global_vars.clone());
+            async move {
+                let status = HealthStatus::from_result(request.send().await);
+                (key.clone(), status)
+            }
+        });
+
+        report.extend(join_all(pings).await);
+        report
+    }
+
+    pub async fn sequence(&self, steps: Vec<Step>) -> Result<Vec<Value>> {
+        let mut outputs: Vec<Value> = Vec::with_capacity(steps.len());
+
+        for step in steps {
+            let mut variables = HashMap::new();
+            if let (Some(extractor), Some(previous)) = (&step.extractor, outputs.last()) {
+                variables.extend(extractor(previous));
+            }
+            variables.extend(step.variables);
+
+            let response = self
+                .request(&step.provider, &step.endpoint)?
+                .vars(variables)
+                .send()
+                .await?
+                .error_for_status()
+                .await?;
+
+            outputs.push(response.json().await?);
+        }
+
+        Ok(outputs)
+    }
+
+    pub fn endpoint_description(&self, provider: &str, endpoint: &str) -> Result<Option<String>> {
+        let config = self.config();
+        let endpoint_config = config.get_provider(provider)?.get_endpoint(endpoint)?;
+        Ok(endpoint_config.description.clone())
+    }
+
+    pub fn endpoint_method(&self, provider: &str, endpoint: &str) -> Result<Method> {
+        let config = self.config();
+        config
+            .get_provider(provider)?
+            .get_endpoint(endpoint)?
+            .method()
+    }
+
+    pub fn required_variables(&self, provider: &str, endpoint: &str) -> Result<Vec<String>> {
+        let config = self.config();
+        let provider_config = config.get_provider(provider)?;
+        let endpoint_config = provider_config.get_endpoint(endpoint)?;
+
+        let mut variables = TemplateEngine::extract_variables(&provider_config.base_url);
+        variables.extend(TemplateEngine::extract_variables(&endpoint_config.path));
+
+        for value in provider_config.headers.values() {
+            variables.extend(TemplateEngine::extract_variables(value));
+        }
+
+        for value in endpoint_config.headers.values() {
+            variables.extend(TemplateEngine::extract_variables(value));
+        }
+
+        for value in provider_config.default_query_params.values() {
+            variables.extend(TemplateEngine::extract_variables(value));
+        }
+
+        for value in endpoint_config.query_params.values() {
+            variables.extend(TemplateEngine::extract_variables(value));
+        }
+
+        let auth = endpoint_config
+            .auth
+            .as_ref()
+            .or(provider_config.auth.as_ref());
+        for template in auth.map(|auth| auth.templates()).unwrap_or_default() {
+            variables.extend(TemplateEngine::extract_variables(template));
+        }
+
+        if let Some(body_template) = &endpoint_config.body_template {
+            variables.extend(TemplateEngine::extract_variables(body_template));
+        }
+
+        if let Some(body) = &endpoint_config.body {
+            variables.extend(TemplateEngine::extract_value_variables(body));
+        }
+
+        if let Some(graphql) = &endpoint_config.graphql {
+            variables.extend(TemplateEngine::extract_variables(&graphql.query));
+            if let Some(template) = &graphql.variables {
+                variables.extend(TemplateEngine::extract_value_variables(template));
+            }
+        }
+
+        variables.sort_unstable();
+        variables.dedup();
+        Ok(variables)
+    }
+}
+
+#[cfg(feature = "tracing")]
+fn format_trace_fields(fields: &HashMap<String, String>) -> String {
+    let mut fields: Vec<_> = fields.iter().collect();
+    fields.sort();
+    fields
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join(",")
+}
 
-        Ok(RequestBuilder::new(
-            &self.client,
-            provider_config,
-            endpoint_config,
-            self.max_retries,
-            self.retry_delay,
-        ))
+fn load_config(
+    path: Option<&str>,
+    strict: bool,
+    validate: bool,
+    https_only: bool,
+) -> Result<Config> {
+    let config = match path {
+        Some(path) if strict => Config::from_file_strict(path)?,
+        Some(path) => Config::from_file(path)?,
+        None => Config::try_default()?,
+    };
+
+    if validate {
+        config.validate()?;
+    }
+    if https_only {
+        config.require_https()?;
     }
+    Ok(config)
+}
+
+#[cfg(feature = "socks")]
+fn socks5_proxy(url: &str) -> Result<reqwest::Proxy> {
+    let scheme = url.split_once("://").map(|(scheme, _)| scheme);
+    if !matches!(scheme, Some("socks5" | "socks5h")) {
+        return Err(CallixError::ValidationError(format!(
+            "socks5_proxy must use the socks5:// or socks5h:// scheme: {}",
+            url
+        )));
+    }
+    Ok(reqwest::Proxy::all(url)?)
 }
 
 #[inline]
@@ -63,3 +500,685 @@ pub fn parse_method(method: &str) -> Result<Method> {
         _ => Err(CallixError::InvalidMethod),
     }
 }
+
+#[inline]
+pub fn is_idempotent(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::PUT | Method::DELETE | Method::OPTIONS
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ReplicaUrl;
+    use crate::test_support::{MockServer, Reply, TempFile, endpoint, mock_client, provider};
+    use reqwest::header::{HeaderMap, HeaderValue};
+    use serde_json::Value;
+    use std::collections::HashMap;
+
+    fn callix() -> Callix {
+        CallixBuilder::new().build().unwrap()
+    }
+
+    #[test]
+    fn required_variables_covers_every_template_source() {
+        let variables = callix().required_variables("openai", "chat").unwrap();
+        assert_eq!(
+            variables,
+            ["API_KEY", "max_tokens", "messages", "model", "temperature"]
+        );
+    }
+
+    #[test]
+    fn required_variables_reports_unknown_endpoints() {
+        let result = callix().required_variables("openai", "missing");
+        assert!(matches!(result, Err(CallixError::EndpointNotFound(_))));
+    }
+
+    fn tagged_client() -> Client {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-shared-client", HeaderValue::from_static("yes"));
+        Client::builder().default_headers(headers).build().unwrap()
+    }
+
+    #[tokio::test]
+    async fn builder_uses_the_provided_client() {
+        let server = MockServer::start(vec![Reply::ok("ok")]).await;
+        let builder = CallixBuilder::new().client(tagged_client());
+        let callix = mock_client(builder, &server, endpoint("GET", "/"));
+
+        callix
+            .request("mock", "call")
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(server.requests()[0].header("x-shared-client"), Some("yes"));
+    }
+
+    #[tokio::test]
+    async fn with_client_uses_the_provided_client_and_config() {
+        let server = MockServer::start(vec![Reply::ok("ok")]).await;
+        let mut mock = provider(&server.url);
+        mock.endpoints
+            .insert("call".to_string(), endpoint("GET", "/"));
+        let config = Config {
+            providers: HashMap::from([("mock".to_string(), mock)]),
+        };
+        let callix = Callix::with_client(tagged_client(), config, 0, Duration::ZERO);
+
+        callix
+            .request("mock", "call")
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(server.requests()[0].header("x-shared-client"), Some("yes"));
+    }
+
+    #[test]
+    fn preview_body_renders_the_openai_chat_body() {
+        let callix = callix();
+        let request = callix
+            .request("openai", "chat")
+            .unwrap()
+            .var("model", "gpt-4o")
+            .var(
+                "messages",
+                serde_json::json!([{"role": "user", "content": "hi"}]),
+            )
+            .var("temperature", 0.5)
+            .var("max_tokens", 32);
+
+        let body: Value = serde_json::from_str(&request.preview_body().unwrap()).unwrap();
+        assert_eq!(body["model"], "gpt-4o");
+        assert_eq!(body["max_tokens"], 32);
+    }
+
+    #[test]
+    fn preview_body_surfaces_template_errors() {
+        let callix = callix();
+        let request = callix
+            .request("openai", "chat")
+            .unwrap()
+            .var("model", "gpt-4o");
+        assert!(matches!(
+            request.preview_body(),
+            Err(CallixError::TemplateError)
+        ));
+    }
+
+    #[tokio::test]
+    async fn registered_providers_and_endpoints_are_requestable() {
+        let server = MockServer::start(vec![Reply::ok("ok")]).await;
+        let mut callix = callix();
+        assert!(callix.request("runtime", "ping").is_err());
+
+        callix
+            .register_provider("runtime", ProviderConfig::new(&server.url))
+            .unwrap();
+        callix
+            .register_endpoint("runtime", "ping", EndpointConfig::new("GET", "/ping"))
+            .unwrap();
+
+        let response = callix
+            .request("runtime", "ping")
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(server.requests()[0].target, "/ping");
+    }
+
+    #[test]
+    fn register_endpoint_requires_a_known_provider() {
+        let mut callix = callix();
+        let result = callix.register_endpoint("missing", "ping", EndpointConfig::new("GET", "/"));
+        assert!(matches!(result, Err(CallixError::ProviderNotFound)));
+    }
+
+    #[tokio::test]
+    async fn global_vars_apply_beneath_request_vars() {
+        let server = MockServer::start(vec![Reply::ok("ok")]).await;
+        let mut callix = callix();
+        let provider = ProviderConfig::new(&server.url)
+            .header("Authorization", "Bearer {{API_KEY}}")
+            .endpoint(
+                "embed",
+                EndpointConfig::new("POST", "/").body_template(r#"{"model": "{{model}}"}"#),
+            );
+        callix.register_provider("mock", provider).unwrap();
+        callix.set_global_var("API_KEY", "global-key");
+        callix.set_global_var("model", "global-model");
+
+        let request = callix
+            .request("mock", "embed")
+            .unwrap()
+            .var("model", "request-model");
+        request.send().await.unwrap();
+
+        let sent = &server.requests()[0];
+        let body: Value = serde_json::from_slice(&sent.body).unwrap();
+        assert_eq!(sent.header("authorization"), Some("Bearer global-key"));
+        assert_eq!(body["model"], "request-model");
+
+        assert_eq!(
+            callix.remove_global_var("API_KEY"),
+            Some(Value::from("global-key"))
+        );
+        let request = callix.request("mock", "embed").unwrap();
+        assert!(matches!(
+            request.send().await,
+            Err(CallixError::TemplateError)
+        ));
+    }
+
+    const INVALID_METHOD: &str = r#"
+providers:
+  api:
+    base_url: "https://api.test"
+    endpoints:
+      ping:
+        path: "/ping"
+        method: "FE TCH"
+"#;
+
+    #[test]
+    fn validate_on_build_rejects_invalid_config_eagerly() {
+        let file = TempFile::new("yaml", INVALID_METHOD);
+
+        let result = CallixBuilder::new().config(file.path_str()).build();
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn disabling_validate_on_build_defers_errors_to_the_request() {
+        let file = TempFile::new("yaml", INVALID_METHOD);
+
+        let callix = CallixBuilder::new()
+            .config(file.path_str())
+            .validate_on_build(false)
+            .build()
+            .unwrap();
+        let request = callix.request("api", "ping").unwrap();
+        assert!(matches!(
+            request.send().await,
+            Err(CallixError::InvalidMethod)
+        ));
+    }
+
+    fn api_config(base_url: &str) -> String {
+        format!(
+            "providers:\n  api:\n    base_url: \"{}\"\n    endpoints:\n      ping:\n        path: \"/ping\"\n        method: \"GET\"\n",
+            base_url
+        )
+    }
+
+    fn ping_url(callix: &Callix) -> String {
+        let request = callix.request("api", "ping").unwrap();
+        request.build_request().unwrap().url.to_string()
+    }
+
+    #[test]
+    fn reload_config_swaps_in_the_new_file() {
+        let file = TempFile::new("yaml", api_config("https://old.test"));
+        let callix = CallixBuilder::new()
+            .config(file.path_str())
+            .build()
+            .unwrap();
+        assert_eq!(ping_url(&callix), "https://old.test/ping");
+
+        std::fs::write(file.path(), api_config("https://new.test")).unwrap();
+        callix.reload_config().unwrap();
+        assert_eq!(ping_url(&callix), "https://new.test/ping");
+    }
+
+    #[test]
+    fn failed_reload_keeps_the_current_config() {
+        let file = TempFile::new("yaml", api_config("https://old.test"));
+        let callix = CallixBuilder::new()
+            .config(file.path_str())
+            .build()
+            .unwrap();
+
+        std::fs::write(file.path(), "providers: [not, a, map]").unwrap();
+        assert!(callix.reload_config().is_err());
+        assert_eq!(ping_url(&callix), "https://old.test/ping");
+    }
+
+    #[test]
+    fn https_only_rejects_plaintext_base_urls() {
+        let file = TempFile::new("yaml", api_config("http://plain.test"));
+
+        let result = CallixBuilder::new()
+            .config(file.path_str())
+            .https_only(true)
+            .build();
+        assert!(matches!(result, Err(CallixError::ValidationError(_))));
+        assert!(CallixBuilder::new().config(file.path_str()).build().is_ok());
+    }
+
+    #[test]
+    fn https_only_accepts_https_and_templated_base_urls() {
+        let secure = TempFile::new("yaml", api_config("https://secure.test"));
+        let templated = TempFile::new("yaml", api_config("{{base}}"));
+
+        for file in [&secure, &templated] {
+            let result = CallixBuilder::new()
+                .config(file.path_str())
+                .https_only(true)
+                .build();
+            assert!(result.is_ok());
+        }
+    }
+
+    #[test]
+    fn https_only_applies_on_reload() {
+        let file = TempFile::new("yaml", api_config("https://secure.test"));
+        let callix = CallixBuilder::new()
+            .config(file.path_str())
+            .https_only(true)
+            .build()
+            .unwrap();
+
+        std::fs::write(file.path(), api_config("http://plain.test")).unwrap();
+        assert!(callix.reload_config().is_err());
+        assert_eq!(ping_url(&callix), "https://secure.test/ping");
+    }
+
+    #[test]
+    fn register_provider_validates_before_inserting() {
+        let mut callix = CallixBuilder::new().https_only(true).build().unwrap();
+
+        let plain = ProviderConfig::new("http://plain.test");
+        assert!(matches!(
+            callix.register_provider("plain", plain),
+            Err(CallixError::ValidationError(_))
+        ));
+        let schemeless = ProviderConfig::new("api.test");
+        assert!(matches!(
+            callix.register_provider("schemeless", schemeless),
+            Err(CallixError::ValidationError(_))
+        ));
+        assert!(callix.config().get_provider("plain").is_err());
+        assert!(callix.config().get_provider("schemeless").is_err());
+
+        let secure = ProviderConfig::new("https://secure.test");
+        callix.register_provider("secure", secure).unwrap();
+        assert!(callix.config().get_provider("secure").is_ok());
+    }
+
+    #[test]
+    fn require_config_rejects_a_missing_config_path() {
+        let result = CallixBuilder::new().require_config(true).build();
+        assert!(matches!(result, Err(CallixError::ConfigNotFound)));
+
+        let file = TempFile::new("yaml", api_config("https://api.test"));
+        let callix = CallixBuilder::new()
+            .require_config(true)
+            .config(file.path_str())
+            .build()
+            .unwrap();
+        assert_eq!(ping_url(&callix), "https://api.test/ping");
+    }
+
+    #[test]
+    fn without_require_config_the_default_config_is_used() {
+        let callix = CallixBuilder::new().build().unwrap();
+        assert!(callix.config().get_provider("openai").is_ok());
+    }
+
+    #[test]
+    fn reload_config_requires_a_config_path() {
+        assert!(matches!(
+            callix().reload_config(),
+            Err(CallixError::ConfigNotFound)
+        ));
+    }
+
+    fn sequence_client(server: &MockServer) -> Callix {
+        let mut callix = callix();
+        let provider = ProviderConfig::new(&server.url)
+            .endpoint("create", EndpointConfig::new("POST", "/users"))
+            .endpoint("fetch", EndpointConfig::new("GET", "/users/{{id}}"));
+        callix.register_provider("mock", provider).unwrap();
+        callix
+    }
+
+    fn fetch_created_user() -> Vec<Step> {
+        vec![
+            Step::new("mock", "create"),
+            Step::new("mock", "fetch")
+                .extract(|previous| HashMap::from([("id".to_string(), previous["id"].clone())])),
+        ]
+    }
+
+    #[tokio::test]
+    async fn sequence_feeds_each_response_into_the_next_step() {
+        let server = MockServer::start(vec![
+            Reply::json(201, r#"{"id": 42}"#),
+            Reply::json(200, r#"{"name": "ann"}"#),
+        ])
+        .await;
+        let callix = sequence_client(&server);
+
+        let outputs = callix.sequence(fetch_created_user()).await.unwrap();
+        assert_eq!(
+            outputs,
+            [
+                serde_json::json!({"id": 42}),
+                serde_json::json!({"name": "ann"})
+            ]
+        );
+        assert_eq!(server.requests()[1].target, "/users/42");
+    }
+
+    #[tokio::test]
+    async fn sequence_stops_at_the_first_failure() {
+        let server = MockServer::start(vec![Reply::json(500, r#"{"error": "down"}"#)]).await;
+        let callix = sequence_client(&server);
+
+        let result = callix.sequence(fetch_created_user()).await;
+        assert!(matches!(
+            result,
+            Err(CallixError::ApiError { status: 500, .. })
+        ));
+        assert_eq!(server.hits(), 1);
+    }
+
+    #[test]
+    fn config_exposes_the_loaded_providers() {
+        let mut callix = callix();
+        let config = callix.config();
+        assert_eq!(
+            config.get_provider("openai").unwrap().base_url,
+            "https://api.openai.com"
+        );
+        assert!(config.providers.contains_key("anthropic"));
+
+        callix
+            .register_provider("runtime", ProviderConfig::new("https://runtime.test"))
+            .unwrap();
+        assert!(callix.config().providers.contains_key("runtime"));
+        assert!(!config.providers.contains_key("runtime"));
+    }
+
+    fn mock_provider(server: &MockServer) -> ProviderConfig {
+        ProviderConfig::new(&server.url).endpoint("call", EndpointConfig::new("GET", "/"))
+    }
+
+    #[tokio::test]
+    async fn local_address_binds_outbound_requests() {
+        let server = MockServer::start(vec![Reply::ok("ok")]).await;
+        let address = std::net::IpAddr::from([127, 0, 0, 1]);
+        let mut callix = CallixBuilder::new().local_address(address).build().unwrap();
+        callix
+            .register_provider("mock", mock_provider(&server))
+            .unwrap();
+
+        let response = callix
+            .request("mock", "call")
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+    }
+
+    #[tokio::test]
+    async fn with_timeout_derives_a_client_with_the_new_timeout() {
+        let slow = Reply::ok("done").delay(Duration::from_millis(300));
+        let server = MockServer::start(vec![slow]).await;
+        let mut callix = CallixBuilder::new().retries(0).build().unwrap();
+        callix
+            .register_provider("mock", mock_provider(&server))
+            .unwrap();
+        let impatient = callix.with_timeout(Duration::from_millis(50));
+
+        let result = impatient.request("mock", "call").unwrap().send().await;
+        assert!(matches!(result, Err(CallixError::TimeoutError)));
+
+        let response = callix.request("mock", "call").unwrap().send().await;
+        assert_eq!(response.unwrap().text().await.unwrap(), "done");
+    }
+
+    #[test]
+    fn with_timeout_shares_config_and_global_vars() {
+        let mut callix = callix();
+        let derived = callix.with_timeout(Duration::from_secs(1));
+
+        callix
+            .register_provider(
+                "late",
+                ProviderConfig::new("https://{{region}}.api.test")
+                    .endpoint("ping", EndpointConfig::new("GET", "/ping")),
+            )
+            .unwrap();
+        callix.set_global_var("region", "eu");
+
+        let request = derived.request("late", "ping").unwrap();
+        assert_eq!(
+            request.build_request().unwrap().url.as_str(),
+            "https://eu.api.test/ping"
+        );
+    }
+
+    fn health_checked(base_url: &str) -> ProviderConfig {
+        ProviderConfig {
+            health_path: Some("/health".to_string()),
+            ..ProviderConfig::new(base_url)
+        }
+        .endpoint("call", EndpointConfig::new("GET", "/call"))
+    }
+
+    #[tokio::test]
+    async fn status_reports_each_provider_with_a_health_path() {
+        let healthy = MockServer::start(vec![Reply::ok("ok")]).await;
+        let failing = MockServer::start(vec![Reply::new(500, &[], "down")]).await;
+        let mut callix = CallixBuilder::new().retries(0).build().unwrap();
+        callix
+            .register_provider("up", health_checked(&healthy.url))
+            .unwrap();
+        callix
+            .register_provider("down", health_checked(&failing.url))
+            .unwrap();
+        callix
+            .register_provider("unchecked", mock_provider(&healthy))
+            .unwrap();
+
+        let status = callix.status().await;
+        assert_eq!(status.len(), 2);
+        assert_eq!(status["up"], HealthStatus::Healthy(200));
+        assert_eq!(status["down"], HealthStatus::Unhealthy(500));
+        assert_eq!(healthy.requests()[0].target, "/health");
+    }
+
+    #[tokio::test]
+    async fn status_checks_every_replica() {
+        let first = MockServer::start(vec![Reply::ok("ok")]).await;
+        let second = MockServer::start(vec![Reply::new(503, &[], "busy")]).await;
+        let provider = ProviderConfig {
+            base_urls: vec![
+                ReplicaUrl::Url(first.url.clone()),
+                ReplicaUrl::Url(second.url.clone()),
+            ],
+            ..health_checked("")
+        };
+        let mut callix = CallixBuilder::new().retries(0).build().unwrap();
+        callix.register_provider("pool", provider).unwrap();
+
+        let status = callix.status().await;
+        assert_eq!(
+            status[&format!("pool@{}", first.url)],
+            HealthStatus::Healthy(200)
+        );
+        assert_eq!(
+            status[&format!("pool@{}", second.url)],
+            HealthStatus::Unhealthy(503)
+        );
+    }
+
+    #[tokio::test]
+    async fn status_reports_open_circuits_without_pinging() {
+        let server = MockServer::start(vec![Reply::new(500, &[], "down")]).await;
+        let mut callix = CallixBuilder::new()
+            .retries(0)
+            .circuit_breaker(1, Duration::from_secs(60))
+            .build()
+            .unwrap();
+        callix
+            .register_provider("flaky", health_checked(&server.url))
+            .unwrap();
+
+        let response = callix.request("flaky", "call").unwrap().send().await;
+        assert_eq!(response.unwrap().status(), 500);
+
+        let status = callix.status().await;
+        assert_eq!(status["flaky"], HealthStatus::CircuitOpen);
+        assert_eq!(server.hits(), 1);
+    }
+
+    #[cfg(feature = "socks")]
+    async fn socks5_mock() -> (String, Arc<std::sync::atomic::AtomicUsize>) {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::net::{TcpListener, TcpStream};
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("socks5://{}", listener.local_addr().unwrap());
+        let tunnels = Arc::new(AtomicUsize::new(0));
+
+        let counter = Arc::clone(&tunnels);
+        tokio::spawn(async move {
+            while let Ok((mut client, _)) = listener.accept().await {
+                counter.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let mut greeting = [0u8; 2];
+                    client.read_exact(&mut greeting).await?;
+                    let mut methods = vec![0u8; greeting[1] as usize];
+                    client.read_exact(&mut methods).await?;
+                    client.write_all(&[5, 0]).await?;
+
+                    let mut head = [0u8; 4];
+                    client.read_exact(&mut head).await?;
+                    let host = match head[3] {
+                        1 => {
+                            let mut ip = [0u8; 4];
+                            client.read_exact(&mut ip).await?;
+                            std::net::Ipv4Addr::from(ip).to_string()
+                        }
+                        _ => {
+                            let mut len = [0u8; 1];
+                            client.read_exact(&mut len).await?;
+                            let mut name = vec![0u8; len[0] as usize];
+                            client.read_exact(&mut name).await?;
+                            String::from_utf8_lossy(&name).into_owned()
+                        }
+                    };
+                    let port = client.read_u16().await?;
+
+                    let mut upstream = TcpStream::connect((host.as_str(), port)).await?;
+                    client.write_all(&[5, 0, 0, 1, 0, 0, 0, 0, 0, 0]).await?;
+                    tokio::io::copy_bidirectional(&mut client, &mut upstream).await?;
+                    std::io::Result::Ok(())
+                });
+            }
+        });
+
+        (url, tunnels)
+    }
+
+    #[cfg(feature = "socks")]
+    #[tokio::test]
+    async fn socks5_proxy_tunnels_requests() {
+        let server = MockServer::start(vec![Reply::ok("via proxy")]).await;
+        let (proxy, tunnels) = socks5_mock().await;
+        let mut callix = CallixBuilder::new().socks5_proxy(proxy).build().unwrap();
+        callix
+            .register_provider("mock", mock_provider(&server))
+            .unwrap();
+
+        let response = callix
+            .request("mock", "call")
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "via proxy");
+        assert_eq!(tunnels.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert_eq!(server.hits(), 1);
+    }
+
+    #[cfg(feature = "socks")]
+    #[test]
+    fn socks5_proxy_requires_a_socks_scheme() {
+        assert!(
+            CallixBuilder::new()
+                .socks5_proxy("socks5h://127.0.0.1:1080")
+                .build()
+                .is_ok()
+        );
+        assert!(matches!(
+            CallixBuilder::new()
+                .socks5_proxy("http://127.0.0.1:1080")
+                .build(),
+            Err(CallixError::ValidationError(_))
+        ));
+    }
+
+    #[test]
+    fn endpoint_method_resolves_the_configured_method() {
+        let mut callix = callix();
+        callix
+            .register_provider(
+                "api",
+                ProviderConfig::new("https://api.test")
+                    .endpoint("create", EndpointConfig::new("post", "/items")),
+            )
+            .unwrap();
+        callix
+            .register_endpoint("api", "bogus", EndpointConfig::new("FETCH", "/items"))
+            .unwrap();
+
+        assert_eq!(
+            callix.endpoint_method("api", "create").unwrap(),
+            Method::POST
+        );
+        assert!(matches!(
+            callix.endpoint_method("api", "bogus"),
+            Err(CallixError::InvalidMethod)
+        ));
+        assert!(matches!(
+            callix.endpoint_method("missing", "create"),
+            Err(CallixError::ProviderNotFound)
+        ));
+        assert!(matches!(
+            callix.endpoint_method("api", "missing"),
+            Err(CallixError::EndpointNotFound(name)) if name == "missing"
+        ));
+    }
+
+    #[test]
+    fn endpoint_description_reads_the_config() {
+        let mut callix = callix();
+        let mut described = EndpointConfig::new("GET", "/ping");
+        described.description = Some("Checks liveness".to_string());
+        let provider = ProviderConfig::new("https://api.test")
+            .endpoint("ping", described)
+            .endpoint("plain", EndpointConfig::new("GET", "/plain"));
+        callix.register_provider("docs", provider).unwrap();
+
+        assert_eq!(
+            callix
+                .endpoint_description("docs", "ping")
+                .unwrap()
+                .as_deref(),
+            Some("Checks liveness")
+        );
+        assert_eq!(callix.endpoint_description("docs", "plain").unwrap(), None);
+        assert!(callix.endpoint_description("docs", "missing").is_err());
+    }
+}
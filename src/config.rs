@@ -1,36 +1,218 @@
+use crate::client::parse_method;
 use crate::error::{CallixError, Result};
+use reqwest::{Method, Url};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::collections::HashMap;
 use std::fs;
+use std::path::Path;
+use std::time::Duration;
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Config {
     pub providers: HashMap<String, ProviderConfig>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct ProviderConfig {
+    #[serde(default)]
+    pub extends: Option<String>,
+    #[serde(default)]
     pub base_url: String,
     #[serde(default)]
+    pub base_urls: Vec<ReplicaUrl>,
+    #[serde(default)]
+    pub load_balance: Option<LoadBalance>,
+    #[serde(default)]
     pub headers: HashMap<String, String>,
+    #[serde(default)]
     pub endpoints: HashMap<String, EndpointConfig>,
     #[serde(default)]
     pub timeout: Option<u64>,
+    #[serde(default)]
+    pub auth: Option<AuthConfig>,
+    #[serde(default)]
+    pub default_body: Option<Value>,
+    #[serde(default)]
+    pub default_query_params: HashMap<String, String>,
+    #[serde(default)]
+    pub default_body_type: Option<BodyType>,
+    #[serde(default)]
+    pub health_path: Option<String>,
+    #[serde(skip)]
+    pub(crate) parsed_base_url: Option<Url>,
+    #[serde(skip)]
+    pub(crate) parsed_base_urls: Vec<Option<Url>>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct EndpointConfig {
     pub path: String,
     pub method: String,
+    #[serde(default)]
+    pub description: Option<String>,
     pub body_template: Option<String>,
     #[serde(default)]
+    pub body_template_file: Option<String>,
+    #[serde(default)]
+    pub body_format: BodyFormat,
+    #[serde(default)]
+    pub body_type: Option<BodyType>,
+    #[serde(default)]
+    pub body: Option<Value>,
+    #[serde(default)]
     pub query_params: HashMap<String, String>,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    #[serde(default)]
+    pub accept: Option<String>,
+    #[serde(default)]
+    pub auth: Option<AuthConfig>,
+    #[serde(default)]
+    pub graphql: Option<GraphqlConfig>,
+    #[serde(default)]
+    pub retry_on_status: Option<Vec<u16>>,
+    #[serde(default)]
+    pub retry_on_body: Option<RetryOnBody>,
+    #[serde(default, alias = "timeout")]
+    pub timeout_ms: Option<u64>,
+    #[serde(skip)]
+    pub(crate) parsed_method: Option<Method>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct RetryOnBody {
+    pub pointer: String,
+    pub values: Vec<Value>,
+}
+
+impl RetryOnBody {
+    pub(crate) fn matches(&self, body: &[u8]) -> bool {
+        serde_json::from_slice::<Value>(body)
+            .ok()
+            .and_then(|body| body.pointer(&self.pointer).cloned())
+            .is_some_and(|value| self.values.contains(&value))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct GraphqlConfig {
+    pub query: String,
+    #[serde(default)]
+    pub variables: Option<Value>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum BodyFormat {
+    #[default]
+    Json,
+    Yaml,
+    #[cfg(feature = "toml")]
+    Toml,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(untagged)]
+pub enum ReplicaUrl {
+    Url(String),
+    Weighted { url: String, weight: u32 },
+}
+
+impl ReplicaUrl {
+    pub fn url(&self) -> &str {
+        match self {
+            Self::Url(url) | Self::Weighted { url, .. } => url,
+        }
+    }
+
+    pub fn weight(&self) -> u32 {
+        match self {
+            Self::Url(_) => 1,
+            Self::Weighted { weight, .. } => *weight,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum LoadBalance {
+    #[default]
+    RoundRobin,
+    Weighted,
+    Random,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum BodyType {
+    #[default]
+    Json,
+    Form,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AuthConfig {
+    None,
+    Bearer {
+        token: String,
+    },
+    BearerFile {
+        path: String,
+    },
+    Basic {
+        username: String,
+        #[serde(default)]
+        password: Option<String>,
+    },
+    Header {
+        name: String,
+        value: String,
+    },
 }
 
 impl Config {
     pub fn from_file(path: &str) -> Result<Self> {
-        let content = fs::read_to_string(path).map_err(|_| CallixError::ConfigNotFound)?;
-        serde_yaml::from_str(&content).map_err(|_| CallixError::InvalidConfig)
+        let content = read_config_file(path)?;
+        let mut config: Self =
+            serde_yaml::from_str(&content).map_err(|_| CallixError::InvalidConfig)?;
+        config.resolve_extends()?;
+        config.load_body_templates(path)?;
+        config.prepare();
+        Ok(config)
+    }
+
+    pub fn from_file_strict(path: &str) -> Result<Self> {
+        let content = read_config_file(path)?;
+        let deserializer = serde_yaml::Deserializer::from_str(&content);
+
+        let mut unknown_field = None;
+        let config: std::result::Result<Self, _> =
+            serde_ignored::deserialize(deserializer, |path| {
+                unknown_field.get_or_insert_with(|| path.to_string());
+            });
+
+        match (config, unknown_field) {
+            (_, Some(field)) => Err(CallixError::UnknownConfigField(field)),
+            (Ok(mut config), None) => {
+                config.resolve_extends()?;
+                config.load_body_templates(path)?;
+                config.prepare();
+                Ok(config)
+            }
+            (Err(_), None) => Err(CallixError::InvalidConfig),
+        }
     }
 
     #[inline]
@@ -40,8 +222,776 @@ impl Config {
             .ok_or(CallixError::ProviderNotFound)
     }
 
+    pub fn providers_with_endpoint(&self, endpoint: &str) -> Vec<&str> {
+        let mut providers: Vec<&str> = self
+            .providers
+            .iter()
+            .filter(|(_, provider)| provider.endpoints.contains_key(endpoint))
+            .map(|(name, _)| name.as_str())
+            .collect();
+        providers.sort_unstable();
+        providers
+    }
+
     pub fn default_config() -> Self {
+        Self::try_default().expect("Default config is invalid")
+    }
+
+    pub fn try_default() -> Result<Self> {
         let yaml = include_str!("../default-config.yaml");
-        serde_yaml::from_str(yaml).expect("Default config is invalid")
+        let mut config: Self =
+            serde_yaml::from_str(yaml).map_err(|_| CallixError::InvalidConfig)?;
+        config.resolve_extends()?;
+        config.prepare();
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let mut providers: Vec<_> = self.providers.iter().collect();
+        providers.sort_by_key(|(name, _)| name.as_str());
+
+        for (name, provider) in providers {
+            provider.validate(name)?;
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn require_https(&self) -> Result<()> {
+        let mut providers: Vec<_> = self.providers.iter().collect();
+        providers.sort_by_key(|(name, _)| name.as_str());
+
+        for (name, provider) in providers {
+            provider.require_https(name)?;
+        }
+
+        Ok(())
+    }
+
+    fn resolve_extends(&mut self) -> Result<()> {
+        let mut names: Vec<_> = self.providers.keys().cloned().collect();
+        names.sort();
+
+        let mut resolved = HashMap::new();
+        for name in &names {
+            self.resolve_provider(name, &mut Vec::new(), &mut resolved)?;
+        }
+
+        self.providers = resolved;
+        Ok(())
+    }
+
+    fn resolve_provider(
+        &self,
+        name: &str,
+        chain: &mut Vec<String>,
+        resolved: &mut HashMap<String, ProviderConfig>,
+    ) -> Result<ProviderConfig> {
+        if let Some(provider) = resolved.get(name) {
+            return Ok(provider.clone());
+        }
+
+        if chain.iter().any(|seen| seen == name) {
+            chain.push(name.to_string());
+            return Err(CallixError::ValidationError(format!(
+                "providers.{}.extends: cycle {}",
+                chain[0],
+                chain.join(" -> ")
+            )));
+        }
+
+        let provider = self.providers.get(name).ok_or_else(|| {
+            CallixError::ValidationError(format!(
+                "providers.{}.extends: unknown provider '{}'",
+                chain.last().map(String::as_str).unwrap_or(name),
+                name
+            ))
+        })?;
+
+        let merged = match &provider.extends {
+            Some(parent) => {
+                chain.push(name.to_string());
+                let parent = self.resolve_provider(parent, chain, resolved)?;
+                chain.pop();
+                provider.clone().inherit(parent)
+            }
+            None => provider.clone(),
+        };
+
+        resolved.insert(name.to_string(), merged.clone());
+        Ok(merged)
+    }
+
+    fn load_body_templates(&mut self, config_path: &str) -> Result<()> {
+        let base_dir = Path::new(config_path).parent().unwrap_or(Path::new(""));
+
+        for (provider_name, provider) in &mut self.providers {
+            for (endpoint_name, endpoint) in &mut provider.endpoints {
+                let Some(file) = &endpoint.body_template_file else {
+                    continue;
+                };
+
+                let field = format!(
+                    "providers.{}.endpoints.{}.body_template_file",
+                    provider_name, endpoint_name
+                );
+                if endpoint.body_template.is_some() {
+                    return Err(CallixError::ValidationError(format!(
+                        "{}: cannot be combined with body_template",
+                        field
+                    )));
+                }
+
+                let template = fs::read_to_string(base_dir.join(file)).map_err(|e| {
+                    CallixError::ValidationError(format!("{}: {}: {}", field, file, e))
+                })?;
+                endpoint.body_template = Some(template);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn prepare(&mut self) {
+        for provider in self.providers.values_mut() {
+            provider.prepare();
+        }
+    }
+}
+
+impl ProviderConfig {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into(),
+            ..Self::default()
+        }
+    }
+
+    pub fn header(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn endpoint(mut self, name: impl Into<String>, endpoint: EndpointConfig) -> Self {
+        self.endpoints.insert(name.into(), endpoint);
+        self
+    }
+
+    fn inherit(mut self, parent: ProviderConfig) -> Self {
+        if self.base_url.is_empty() {
+            self.base_url = parent.base_url;
+        }
+        if self.base_urls.is_empty() {
+            self.base_urls = parent.base_urls;
+        }
+
+        for (key, value) in parent.headers {
+            if !self
+                .headers
+                .keys()
+                .any(|own| own.eq_ignore_ascii_case(&key))
+            {
+                self.headers.insert(key, value);
+            }
+        }
+        for (name, endpoint) in parent.endpoints {
+            self.endpoints.entry(name).or_insert(endpoint);
+        }
+        for (key, value) in parent.default_query_params {
+            self.default_query_params.entry(key).or_insert(value);
+        }
+
+        self.timeout = self.timeout.or(parent.timeout);
+        self.auth = self.auth.or(parent.auth);
+        self.default_body = self.default_body.or(parent.default_body);
+        self.default_body_type = self.default_body_type.or(parent.default_body_type);
+        self.health_path = self.health_path.or(parent.health_path);
+        self.load_balance = self.load_balance.or(parent.load_balance);
+        self
+    }
+
+    pub(crate) fn prepare(&mut self) {
+        self.parsed_base_url = parse_static_url(&self.base_url);
+        self.parsed_base_urls = self
+            .base_urls
+            .iter()
+            .map(|replica| parse_static_url(replica.url()))
+            .collect();
+        for endpoint in self.endpoints.values_mut() {
+            endpoint.prepare();
+        }
+    }
+
+    pub(crate) fn validate(&self, name: &str) -> Result<()> {
+        for replica in &self.base_urls {
+            if !replica.url().contains("{{") {
+                check_base_url(name, replica.url())?;
+            }
+        }
+        let replicas_only = self.base_url.is_empty() && !self.base_urls.is_empty();
+        if !self.base_url.contains("{{") && !replicas_only {
+            check_base_url(name, &self.base_url)?;
+        }
+
+        let mut endpoints: Vec<_> = self.endpoints.iter().collect();
+        endpoints.sort_by_key(|(name, _)| name.as_str());
+
+        for (endpoint_name, endpoint) in endpoints {
+            if parse_method(&endpoint.method).is_err() {
+                return Err(CallixError::ValidationError(format!(
+                    "providers.{}.endpoints.{}.method: invalid HTTP method '{}'",
+                    name, endpoint_name, endpoint.method
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn require_https(&self, name: &str) -> Result<()> {
+        let urls = std::iter::once(self.base_url.as_str())
+            .chain(self.base_urls.iter().map(ReplicaUrl::url));
+        for url in urls {
+            if url.contains("{{") {
+                continue;
+            }
+            if Url::parse(url).is_ok_and(|parsed| parsed.scheme() != "https") {
+                return Err(CallixError::ValidationError(format!(
+                    "providers.{}.base_url: '{}' is not https",
+                    name, url
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    pub fn get_endpoint(&self, name: &str) -> Result<&EndpointConfig> {
+        self.endpoints
+            .get(name)
+            .ok_or_else(|| CallixError::EndpointNotFound(name.to_string()))
+    }
+}
+
+impl EndpointConfig {
+    pub fn new(method: impl Into<String>, path: impl Into<String>) -> Self {
+        Self {
+            method: method.into(),
+            path: path.into(),
+            ..Self::default()
+        }
+    }
+
+    pub fn body_template(mut self, template: impl Into<String>) -> Self {
+        self.body_template = Some(template.into());
+        self
+    }
+
+    pub(crate) fn prepare(&mut self) {
+        self.parsed_method = parse_method(&self.method).ok();
+    }
+
+    pub(crate) fn method(&self) -> Result<Method> {
+        match &self.parsed_method {
+            Some(method) => Ok(method.clone()),
+            None => parse_method(&self.method),
+        }
+    }
+
+    pub fn body(mut self, body: Value) -> Self {
+        self.body = Some(body);
+        self
+    }
+
+    pub fn empty_body(self) -> Self {
+        self.body(Value::Object(Default::default()))
+    }
+
+    pub fn graphql(mut self, query: impl Into<String>) -> Self {
+        self.graphql = Some(GraphqlConfig {
+            query: query.into(),
+            variables: None,
+        });
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout_ms = Some(timeout.as_millis() as u64);
+        self
+    }
+
+    pub fn retry_on_status(mut self, statuses: &[u16]) -> Self {
+        self.retry_on_status = Some(statuses.to_vec());
+        self
+    }
+
+    pub fn retry_on_body(mut self, pointer: impl Into<String>, values: Vec<Value>) -> Self {
+        self.retry_on_body = Some(RetryOnBody {
+            pointer: pointer.into(),
+            values,
+        });
+        self
+    }
+
+    pub fn query_param(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.query_params.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn header(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn accept(mut self, media_type: impl Into<String>) -> Self {
+        self.accept = Some(media_type.into());
+        self
+    }
+}
+
+fn parse_static_url(url: &str) -> Option<Url> {
+    if url.contains("{{") {
+        None
+    } else {
+        Url::parse(url).ok()
+    }
+}
+
+fn check_base_url(provider: &str, base_url: &str) -> Result<()> {
+    let url = Url::parse(base_url);
+    if let Ok(url) = &url
+        && matches!(url.scheme(), "http" | "https")
+    {
+        return Ok(());
+    }
+
+    let with_scheme = format!("https://{}", base_url);
+    let message = match url {
+        Ok(url) if url.cannot_be_a_base() && Url::parse(&with_scheme).is_ok() => {
+            format!("'{}' has no scheme; use '{}'", base_url, with_scheme)
+        }
+        Ok(url) => format!(
+            "unsupported scheme '{}' in '{}'; use http or https",
+            url.scheme(),
+            base_url
+        ),
+        Err(_)
+            if !base_url.is_empty()
+                && !base_url.contains("://")
+                && Url::parse(&with_scheme).is_ok() =>
+        {
+            format!("'{}' has no scheme; use '{}'", base_url, with_scheme)
+        }
+        Err(_) => format!("invalid URL '{}'", base_url),
+    };
+
+    Err(CallixError::ValidationError(format!(
+        "providers.{}.base_url: {}",
+        provider, message
+    )))
+}
+
+impl AuthConfig {
+    pub(crate) fn overrides_header(&self, header: &str) -> bool {
+        match self {
+            Self::Header { name, .. } if header.eq_ignore_ascii_case(name) => true,
+            _ => header.eq_ignore_ascii_case("authorization"),
+        }
+    }
+
+    pub(crate) fn templates(&self) -> Vec<&str> {
+        match self {
+            Self::None => Vec::new(),
+            Self::Bearer { token } => vec![token],
+            Self::BearerFile { .. } => Vec::new(),
+            Self::Basic { username, password } => {
+                let mut templates = vec![username.as_str()];
+                templates.extend(password.as_deref());
+                templates
+            }
+            Self::Header { value, .. } => vec![value],
+        }
+    }
+}
+
+#[cfg(feature = "schema")]
+pub fn config_schema() -> Value {
+    serde_json::to_value(schemars::schema_for!(Config)).unwrap_or(Value::Null)
+}
+
+fn read_config_file(path: &str) -> Result<String> {
+    let bytes = fs::read(path).map_err(|_| CallixError::ConfigNotFound)?;
+    let content = String::from_utf8(bytes).map_err(|e| {
+        CallixError::ValidationError(format!(
+            "{} is not valid UTF-8 (invalid byte at offset {})",
+            path,
+            e.utf8_error().valid_up_to()
+        ))
+    })?;
+
+    Ok(match content.strip_prefix('\u{feff}') {
+        Some(stripped) => stripped.to_string(),
+        None => content,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempFile;
+
+    const MISSPELLED: &str = r#"
+providers:
+  api:
+    base_urll: "https://api.test"
+    base_url: "https://api.test"
+    endpoints:
+      ping:
+        path: "/ping"
+        method: "GET"
+"#;
+
+    #[test]
+    fn strict_loading_names_the_unknown_field() {
+        let file = TempFile::new("yaml", MISSPELLED);
+
+        match Config::from_file_strict(file.path_str()) {
+            Err(CallixError::UnknownConfigField(field)) => {
+                assert_eq!(field, "providers.api.base_urll");
+            }
+            other => panic!("expected an unknown field error, got {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn strict_loading_accepts_timeout_as_an_endpoint_alias() {
+        let file = TempFile::new(
+            "yaml",
+            r#"
+providers:
+  api:
+    base_url: "https://api.test"
+    endpoints:
+      export:
+        path: "/export"
+        method: "GET"
+        timeout: 2500
+"#,
+        );
+
+        let config = Config::from_file_strict(file.path_str()).unwrap();
+        let endpoint = config
+            .get_provider("api")
+            .unwrap()
+            .get_endpoint("export")
+            .unwrap();
+        assert_eq!(endpoint.timeout_ms, Some(2500));
+    }
+
+    #[test]
+    fn lenient_loading_ignores_unknown_fields() {
+        let file = TempFile::new("yaml", MISSPELLED);
+
+        let config = Config::from_file(file.path_str()).unwrap();
+        assert!(
+            config
+                .get_provider("api")
+                .unwrap()
+                .get_endpoint("ping")
+                .is_ok()
+        );
+    }
+
+    #[cfg(feature = "schema")]
+    #[test]
+    fn schema_describes_providers_and_endpoints() {
+        let schema = config_schema().to_string();
+        assert!(schema.contains("\"providers\""));
+        assert!(schema.contains("\"endpoints\""));
+        assert!(schema.contains("\"base_url\""));
+    }
+
+    fn resolved(yaml: &str) -> Result<Config> {
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.resolve_extends()?;
+        Ok(config)
+    }
+
+    #[test]
+    fn extending_providers_inherit_and_override() {
+        let config = resolved(
+            r#"
+providers:
+  openai:
+    base_url: "https://api.openai.com"
+    headers:
+      Authorization: "Bearer {{API_KEY}}"
+      X-Model: "gpt-3.5"
+    endpoints:
+      chat:
+        path: "/v1/chat/completions"
+        method: "POST"
+  openai_gpt4:
+    extends: openai
+    headers:
+      X-Model: "gpt-4"
+"#,
+        )
+        .unwrap();
+
+        let child = config.get_provider("openai_gpt4").unwrap();
+        assert_eq!(child.base_url, "https://api.openai.com");
+        assert_eq!(child.headers["X-Model"], "gpt-4");
+        assert_eq!(child.headers["Authorization"], "Bearer {{API_KEY}}");
+        assert_eq!(
+            child.get_endpoint("chat").unwrap().path,
+            "/v1/chat/completions"
+        );
+    }
+
+    #[test]
+    fn extends_cycles_are_rejected() {
+        let result = resolved(
+            r#"
+providers:
+  a:
+    base_url: "https://a.test"
+    extends: b
+  b:
+    base_url: "https://b.test"
+    extends: a
+"#,
+        );
+
+        match result {
+            Err(CallixError::ValidationError(message)) => {
+                assert_eq!(message, "providers.a.extends: cycle a -> b -> a");
+            }
+            other => panic!("expected a cycle error, got {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn extends_requires_a_known_provider() {
+        let result = resolved(
+            r#"
+providers:
+  a:
+    base_url: "https://a.test"
+    extends: missing
+"#,
+        );
+        assert!(matches!(result, Err(CallixError::ValidationError(_))));
+    }
+
+    fn config_with_template_file(template: &TempFile, inline: bool) -> TempFile {
+        let name = template.path().file_name().unwrap().to_str().unwrap();
+        let inline = if inline {
+            "\n        body_template: \"{}\""
+        } else {
+            ""
+        };
+        TempFile::new(
+            "yaml",
+            format!(
+                "providers:\n  api:\n    base_url: \"https://api.test\"\n    endpoints:\n      run:\n        path: \"/run\"\n        method: \"POST\"\n        body_template_file: \"{}\"{}\n",
+                name, inline
+            ),
+        )
+    }
+
+    #[test]
+    fn body_template_file_is_read_relative_to_the_config() {
+        let template = TempFile::new("json", r#"{"model": "{{model}}"}"#);
+        let file = config_with_template_file(&template, false);
+
+        let config = Config::from_file(file.path_str()).unwrap();
+        let endpoint = config
+            .get_provider("api")
+            .unwrap()
+            .get_endpoint("run")
+            .unwrap();
+        assert_eq!(
+            endpoint.body_template.as_deref(),
+            Some(r#"{"model": "{{model}}"}"#)
+        );
+
+        let client = reqwest::Client::new();
+        let provider = config.get_provider("api").unwrap();
+        let request =
+            crate::RequestBuilder::new(&client, provider, endpoint, 0, std::time::Duration::ZERO)
+                .var("model", "m-1");
+        assert_eq!(request.preview_body().unwrap(), r#"{"model": "m-1"}"#);
+    }
+
+    #[test]
+    fn body_template_file_cannot_be_combined_with_an_inline_template() {
+        let template = TempFile::new("json", "{}");
+        let file = config_with_template_file(&template, true);
+
+        match Config::from_file(file.path_str()) {
+            Err(CallixError::ValidationError(message)) => {
+                assert!(message.contains("cannot be combined with body_template"));
+            }
+            other => panic!("expected a validation error, got {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn missing_body_template_files_are_reported() {
+        let missing = TempFile::new("json", "{}");
+        let file = config_with_template_file(&missing, false);
+        drop(missing);
+
+        assert!(matches!(
+            Config::from_file(file.path_str()),
+            Err(CallixError::ValidationError(_))
+        ));
+    }
+
+    const PING: &str = "providers:\n  api:\n    base_url: \"https://api.test\"\n    endpoints:\n      ping:\n        path: \"/ping\"\n        method: \"GET\"\n";
+
+    #[test]
+    fn loads_configs_with_a_utf8_bom() {
+        let file = TempFile::new("yaml", format!("\u{feff}{}", PING));
+
+        let config = Config::from_file(file.path_str()).unwrap();
+        assert!(config.get_provider("api").is_ok());
+
+        let config = Config::from_file_strict(file.path_str()).unwrap();
+        assert!(config.get_provider("api").is_ok());
+    }
+
+    #[test]
+    fn rejects_non_utf8_configs_with_the_offset() {
+        let mut content = PING.as_bytes().to_vec();
+        content.insert(10, 0xff);
+        let file = TempFile::new("yaml", content);
+
+        match Config::from_file(file.path_str()) {
+            Err(CallixError::ValidationError(message)) => {
+                assert!(message.ends_with("is not valid UTF-8 (invalid byte at offset 10)"));
+            }
+            other => panic!("expected a UTF-8 error, got {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn shipped_default_config_loads() {
+        let config = Config::try_default().unwrap();
+        for provider in [
+            "openai",
+            "anthropic",
+            "gemini",
+            "azure_openai",
+            "my_custom_service",
+        ] {
+            assert!(
+                config.get_provider(provider).is_ok(),
+                "missing {}",
+                provider
+            );
+        }
+        config.validate().unwrap();
+    }
+
+    #[test]
+    fn providers_with_endpoint_lists_every_match_sorted() {
+        let config = Config::try_default().unwrap();
+        assert_eq!(
+            config.providers_with_endpoint("chat"),
+            ["azure_openai", "openai"]
+        );
+        assert_eq!(config.providers_with_endpoint("messages"), ["anthropic"]);
+        assert!(config.providers_with_endpoint("missing").is_empty());
+    }
+
+    #[test]
+    fn prepare_caches_the_parsed_method() {
+        let mut endpoint = EndpointConfig::new("post", "/run");
+        endpoint.prepare();
+        endpoint.method = "BOGUS".to_string();
+
+        assert_eq!(endpoint.method().unwrap(), Method::POST);
+    }
+
+    #[test]
+    fn unprepared_endpoints_parse_the_method_on_demand() {
+        assert_eq!(
+            EndpointConfig::new("DELETE", "/run").method().unwrap(),
+            Method::DELETE
+        );
+        assert!(matches!(
+            EndpointConfig::new("Get", "/run").method(),
+            Err(CallixError::InvalidMethod)
+        ));
+    }
+
+    fn base_url_error(base_url: &str) -> String {
+        match check_base_url("api", base_url) {
+            Err(CallixError::ValidationError(message)) => message,
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn schemeless_base_urls_suggest_https() {
+        assert_eq!(
+            base_url_error("api.example.com"),
+            "providers.api.base_url: 'api.example.com' has no scheme; use 'https://api.example.com'"
+        );
+        assert_eq!(
+            base_url_error("localhost:8080/v1"),
+            "providers.api.base_url: 'localhost:8080/v1' has no scheme; use 'https://localhost:8080/v1'"
+        );
+    }
+
+    #[test]
+    fn unsupported_schemes_and_invalid_urls_are_rejected() {
+        assert_eq!(
+            base_url_error("ftp://files.test"),
+            "providers.api.base_url: unsupported scheme 'ftp' in 'ftp://files.test'; use http or https"
+        );
+        assert_eq!(
+            base_url_error("https://"),
+            "providers.api.base_url: invalid URL 'https://'"
+        );
+        assert!(check_base_url("api", "http://localhost:8080").is_ok());
+    }
+
+    #[test]
+    fn validate_reports_a_schemeless_base_url() {
+        let mut config = Config::try_default().unwrap();
+        config.providers.insert(
+            "bare".to_string(),
+            ProviderConfig::new("api.example.com")
+                .endpoint("ping", EndpointConfig::new("GET", "/")),
+        );
+
+        match config.validate() {
+            Err(CallixError::ValidationError(message)) => {
+                assert!(
+                    message.starts_with("providers.bare.base_url:"),
+                    "{}",
+                    message
+                );
+            }
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn retry_on_body_matches_the_pointed_value() {
+        let rule = RetryOnBody {
+            pointer: "/error/code".to_string(),
+            values: vec![Value::from(503), Value::from("busy")],
+        };
+
+        assert!(rule.matches(br#"{"error":{"code":503}}"#));
+        assert!(rule.matches(br#"{"error":{"code":"busy"}}"#));
+        assert!(!rule.matches(br#"{"error":{"code":400}}"#));
+        assert!(!rule.matches(br#"{"data":[]}"#));
+        assert!(!rule.matches(b"<html>busy</html>"));
     }
 }
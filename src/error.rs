@@ -13,6 +13,29 @@ pub enum CallixError {
     TimeoutError,
     MaxRetriesExceeded,
     InvalidMethod,
+    JsonError(serde_json::Error),
+    UrlExpired,
+    UnknownConfigField(String),
+    ApiError {
+        status: u16,
+        body: String,
+    },
+    InvalidUrl(String),
+    JsonPointerNotFound(String),
+    ValidationError(String),
+    ResponseTooLarge(usize),
+    InvalidUtf8(std::str::Utf8Error),
+    IoError(std::io::Error),
+    InvalidVariable {
+        name: String,
+        reason: String,
+    },
+    InvalidBody(String),
+    UnexpectedContentType {
+        expected: String,
+        actual: Option<String>,
+    },
+    CircuitOpen(String),
 }
 
 impl fmt::Display for CallixError {
@@ -27,20 +50,153 @@ impl fmt::Display for CallixError {
             Self::TimeoutError => write!(f, "Request timeout"),
             Self::MaxRetriesExceeded => write!(f, "Max retries exceeded"),
             Self::InvalidMethod => write!(f, "Invalid HTTP method"),
+            Self::JsonError(e) => write!(f, "JSON error: {}", e),
+            Self::UrlExpired => write!(f, "Signed URL has expired"),
+            Self::UnknownConfigField(path) => write!(f, "Unknown config field: {}", path),
+            Self::ApiError { status, body } => write!(f, "API error ({}): {}", status, body),
+            Self::InvalidUrl(url) => write!(f, "Invalid URL: {}", url),
+            Self::JsonPointerNotFound(pointer) => write!(f, "JSON pointer not found: {}", pointer),
+            Self::ValidationError(message) => write!(f, "Validation error: {}", message),
+            Self::ResponseTooLarge(limit) => {
+                write!(f, "Response body exceeds the {} byte limit", limit)
+            }
+            Self::InvalidUtf8(e) => write!(f, "Response body is not valid UTF-8: {}", e),
+            Self::IoError(e) => write!(f, "I/O error: {}", e),
+            Self::InvalidVariable { name, reason } => {
+                write!(f, "Invalid variable value for '{}': {}", name, reason)
+            }
+            Self::InvalidBody(message) => write!(f, "Invalid request body: {}", message),
+            Self::UnexpectedContentType { expected, actual } => write!(
+                f,
+                "Unexpected content type: expected {}, got {}",
+                expected,
+                actual.as_deref().unwrap_or("none")
+            ),
+            Self::CircuitOpen(provider) => write!(f, "Circuit open for provider: {}", provider),
+        }
+    }
+}
+
+impl CallixError {
+    pub fn is_retriable(&self) -> bool {
+        match self {
+            Self::HttpError(e) => e.is_timeout() || e.is_connect(),
+            Self::TimeoutError => true,
+            Self::ApiError { status, .. } => (500..600).contains(status),
+            _ => false,
         }
     }
 }
 
 impl std::error::Error for CallixError {}
 
+#[derive(Debug)]
+pub enum TypedError<E> {
+    Api { status: u16, error: E },
+    Callix(CallixError),
+}
+
+impl<E: fmt::Debug> fmt::Display for TypedError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Api { status, error } => write!(f, "API error ({}): {:?}", status, error),
+            Self::Callix(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl<E: fmt::Debug> std::error::Error for TypedError<E> {}
+
+impl<E> From<CallixError> for TypedError<E> {
+    fn from(err: CallixError) -> Self {
+        Self::Callix(err)
+    }
+}
+
 impl From<reqwest::Error> for CallixError {
     fn from(err: reqwest::Error) -> Self {
         Self::HttpError(err)
     }
 }
 
+impl From<std::io::Error> for CallixError {
+    fn from(err: std::io::Error) -> Self {
+        Self::IoError(err)
+    }
+}
+
 impl From<serde_json::Error> for CallixError {
-    fn from(_: serde_json::Error) -> Self {
-        Self::TemplateError
+    fn from(err: serde_json::Error) -> Self {
+        Self::JsonError(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{MockServer, Reply};
+    use std::time::Duration;
+
+    fn api_error(status: u16) -> CallixError {
+        CallixError::ApiError {
+            status,
+            body: String::new(),
+        }
+    }
+
+    #[test]
+    fn server_errors_and_timeouts_are_retriable() {
+        assert!(CallixError::TimeoutError.is_retriable());
+        assert!(api_error(500).is_retriable());
+        assert!(api_error(503).is_retriable());
+        assert!(api_error(599).is_retriable());
+    }
+
+    #[test]
+    fn client_and_local_errors_are_not_retriable() {
+        assert!(!api_error(400).is_retriable());
+        assert!(!api_error(404).is_retriable());
+        assert!(!api_error(429).is_retriable());
+        assert!(!CallixError::ConfigNotFound.is_retriable());
+        assert!(!CallixError::InvalidConfig.is_retriable());
+        assert!(!CallixError::TemplateError.is_retriable());
+        assert!(!CallixError::MaxRetriesExceeded.is_retriable());
+        assert!(!CallixError::InvalidUrl("x".to_string()).is_retriable());
+        assert!(!CallixError::CircuitOpen("p".to_string()).is_retriable());
+    }
+
+    #[tokio::test]
+    async fn transport_errors_are_classified_by_kind() {
+        let refused = reqwest::get("http://127.0.0.1:1/").await.unwrap_err();
+        assert!(CallixError::from(refused).is_retriable());
+
+        let server = MockServer::start(vec![Reply::ok("late").delay(Duration::from_secs(5))]).await;
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(20))
+            .build()
+            .unwrap();
+        let timed_out = client.get(&server.url).send().await.unwrap_err();
+        assert!(CallixError::from(timed_out).is_retriable());
+
+        let invalid = reqwest::Client::new().get("http://").build().unwrap_err();
+        assert!(!CallixError::from(invalid).is_retriable());
+    }
+
+    #[test]
+    fn serde_json_errors_convert_to_json_error() {
+        let error = serde_json::from_str::<serde_json::Value>("{").unwrap_err();
+        assert!(matches!(
+            CallixError::from(error),
+            CallixError::JsonError(_)
+        ));
+    }
+
+    #[test]
+    fn validation_errors_display_a_generic_prefix() {
+        let error = CallixError::ValidationError("providers.a.extends: cycle a -> a".to_string());
+        assert_eq!(
+            error.to_string(),
+            "Validation error: providers.a.extends: cycle a -> a"
+        );
     }
 }
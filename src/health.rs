@@ -0,0 +1,53 @@
+use crate::error::Result;
+use crate::response::CallixResponse;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HealthStatus {
+    Healthy(u16),
+    Unhealthy(u16),
+    Unreachable(String),
+    CircuitOpen,
+}
+
+impl HealthStatus {
+    #[inline]
+    pub fn is_healthy(&self) -> bool {
+        matches!(self, Self::Healthy(_))
+    }
+
+    pub(crate) fn from_result(result: Result<CallixResponse>) -> Self {
+        match result {
+            Ok(response) if (200..300).contains(&response.status()) => {
+                Self::Healthy(response.status())
+            }
+            Ok(response) => Self::Unhealthy(response.status()),
+            Err(e) => Self::Unreachable(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::CallixError;
+    use crate::test_support::{Reply, respond};
+
+    #[tokio::test]
+    async fn from_result_classifies_statuses_and_errors() {
+        let healthy = respond(Reply::new(204, &[], "")).await;
+        let failing = respond(Reply::new(503, &[], "busy")).await;
+
+        assert_eq!(
+            HealthStatus::from_result(Ok(healthy)),
+            HealthStatus::Healthy(204)
+        );
+        assert_eq!(
+            HealthStatus::from_result(Ok(failing)),
+            HealthStatus::Unhealthy(503)
+        );
+        assert!(matches!(
+            HealthStatus::from_result(Err(CallixError::TimeoutError)),
+            HealthStatus::Unreachable(_)
+        ));
+    }
+}
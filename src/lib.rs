@@ -1,31 +1,95 @@
+mod balance;
+pub mod batch;
+mod circuit;
 pub mod client;
 pub mod config;
 pub mod error;
+pub mod health;
+pub mod middleware;
 pub mod request;
 pub mod response;
+mod retry;
+pub mod sequence;
+pub mod sleeper;
+pub mod source;
 pub mod template;
+#[cfg(test)]
+mod test_support;
+mod token_file;
 
+pub use batch::BatchResult;
 pub use client::Callix;
-pub use error::{CallixError, Result};
-pub use request::RequestBuilder;
-pub use response::CallixResponse;
+#[cfg(feature = "schema")]
+pub use config::config_schema;
+pub use error::{CallixError, Result, TypedError};
+pub use health::HealthStatus;
+pub use middleware::{Middleware, Next};
+pub use request::{Explanation, PreparedRequest, RequestBuilder, RequestDiagnostics};
+pub use response::{CallixResponse, Cookie};
+pub use sequence::Step;
+pub use sleeper::{Sleeper, TokioSleeper};
+pub use source::VariableSource;
 
+use reqwest::Client;
+use retry::RetryPolicy;
+#[cfg(feature = "tracing")]
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
 use std::time::Duration;
 
 pub struct CallixBuilder {
     config_path: Option<String>,
+    strict_config: bool,
+    require_config: bool,
+    validate_on_build: bool,
     timeout: Duration,
-    max_retries: u32,
-    retry_delay: Duration,
+    retry: RetryPolicy,
+    check_url_expiry: bool,
+    middlewares: Vec<Arc<dyn Middleware>>,
+    sort_keys: bool,
+    request_id_header: Option<String>,
+    max_response_size: Option<usize>,
+    accept_encoding: Option<String>,
+    default_headers: Vec<(String, String)>,
+    success_statuses: Option<Vec<u16>>,
+    circuit_breaker: Option<(u32, Duration)>,
+    hedge: Option<Duration>,
+    local_address: Option<IpAddr>,
+    https_only: bool,
+    #[cfg(feature = "socks")]
+    socks5_proxy: Option<String>,
+    #[cfg(feature = "tracing")]
+    trace_fields: HashMap<String, String>,
+    client: Option<Client>,
 }
 
 impl Default for CallixBuilder {
     fn default() -> Self {
         Self {
             config_path: None,
+            strict_config: false,
+            require_config: false,
+            validate_on_build: true,
             timeout: Duration::from_secs(30),
-            max_retries: 3,
-            retry_delay: Duration::from_secs(1),
+            retry: RetryPolicy::new(3, Duration::from_secs(1)),
+            check_url_expiry: false,
+            middlewares: Vec::new(),
+            sort_keys: false,
+            request_id_header: None,
+            max_response_size: None,
+            accept_encoding: None,
+            default_headers: Vec::new(),
+            success_statuses: None,
+            circuit_breaker: None,
+            hedge: None,
+            local_address: None,
+            https_only: false,
+            #[cfg(feature = "socks")]
+            socks5_proxy: None,
+            #[cfg(feature = "tracing")]
+            trace_fields: HashMap::new(),
+            client: None,
         }
     }
 }
@@ -41,27 +105,221 @@ impl CallixBuilder {
         self
     }
 
+    pub fn strict_config(mut self, enabled: bool) -> Self {
+        self.strict_config = enabled;
+        self
+    }
+
+    pub fn require_config(mut self, enabled: bool) -> Self {
+        self.require_config = enabled;
+        self
+    }
+
+    pub fn validate_on_build(mut self, enabled: bool) -> Self {
+        self.validate_on_build = enabled;
+        self
+    }
+
     pub fn timeout(mut self, duration: Duration) -> Self {
         self.timeout = duration;
         self
     }
 
     pub fn retries(mut self, count: u32) -> Self {
-        self.max_retries = count;
+        self.retry.max_retries = count;
         self
     }
 
     pub fn retry_delay(mut self, duration: Duration) -> Self {
-        self.retry_delay = duration;
+        self.retry.retry_delay = duration;
+        self
+    }
+
+    pub fn retry_delay_fn(
+        mut self,
+        delay_fn: impl Fn(u32, Option<&CallixError>) -> Duration + Send + Sync + 'static,
+    ) -> Self {
+        self.retry.delay_fn = Some(Arc::new(delay_fn));
+        self
+    }
+
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.retry.max_attempts = Some(attempts);
+        self
+    }
+
+    pub fn max_delay(mut self, duration: Duration) -> Self {
+        self.retry.max_delay = Some(duration);
+        self
+    }
+
+    pub fn sleeper(mut self, sleeper: impl Sleeper + 'static) -> Self {
+        self.retry.sleeper = Arc::new(sleeper);
+        self
+    }
+
+    pub fn total_deadline(mut self, duration: Duration) -> Self {
+        self.retry.total_deadline = Some(duration);
+        self
+    }
+
+    pub fn retry_on_status(mut self, statuses: &[u16]) -> Self {
+        self.retry.retry_statuses = statuses.to_vec();
+        self
+    }
+
+    pub fn retry_unsafe_methods(mut self, enabled: bool) -> Self {
+        self.retry.retry_unsafe_methods = enabled;
+        self
+    }
+
+    pub fn check_url_expiry(mut self, enabled: bool) -> Self {
+        self.check_url_expiry = enabled;
+        self
+    }
+
+    pub fn sort_keys(mut self, enabled: bool) -> Self {
+        self.sort_keys = enabled;
+        self
+    }
+
+    pub fn auto_request_id(mut self, header_name: impl Into<String>) -> Self {
+        self.request_id_header = Some(header_name.into());
+        self
+    }
+
+    pub fn max_response_size(mut self, bytes: usize) -> Self {
+        self.max_response_size = Some(bytes);
+        self
+    }
+
+    pub fn accept_encoding(mut self, encoding: impl Into<String>) -> Self {
+        self.accept_encoding = Some(encoding.into());
+        self
+    }
+
+    pub fn default_header(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        let key = key.into().to_ascii_lowercase();
+        self.default_headers
+            .retain(|(existing, _)| *existing != key);
+        self.default_headers.push((key, value.into()));
+        self
+    }
+
+    pub fn referer(self, referer: impl Into<String>) -> Self {
+        self.default_header("referer", referer)
+    }
+
+    pub fn origin(self, origin: impl Into<String>) -> Self {
+        self.default_header("origin", origin)
+    }
+
+    pub fn success_statuses(mut self, statuses: &[u16]) -> Self {
+        self.success_statuses = Some(statuses.to_vec());
+        self
+    }
+
+    pub fn circuit_breaker(mut self, failures: u32, cooldown: Duration) -> Self {
+        self.circuit_breaker = Some((failures, cooldown));
+        self
+    }
+
+    pub fn hedge(mut self, delay: Duration) -> Self {
+        self.hedge = Some(delay);
+        self
+    }
+
+    pub fn local_address(mut self, address: IpAddr) -> Self {
+        self.local_address = Some(address);
+        self
+    }
+
+    pub fn https_only(mut self, enabled: bool) -> Self {
+        self.https_only = enabled;
+        self
+    }
+
+    #[cfg(feature = "socks")]
+    pub fn socks5_proxy(mut self, url: impl Into<String>) -> Self {
+        self.socks5_proxy = Some(url.into());
+        self
+    }
+
+    #[cfg(feature = "tracing")]
+    pub fn trace_fields(mut self, fields: HashMap<String, String>) -> Self {
+        self.trace_fields.extend(fields);
+        self
+    }
+
+    pub fn client(mut self, client: Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    pub fn with(mut self, middleware: impl Middleware + 'static) -> Self {
+        self.middlewares.push(Arc::new(middleware));
         self
     }
 
     pub fn build(self) -> Result<Callix> {
-        Callix::new(
-            self.config_path,
-            self.timeout,
-            self.max_retries,
-            self.retry_delay,
-        )
+        Callix::from_builder(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{EndpointConfig, ProviderConfig};
+    use crate::test_support::{MockServer, Reply, mock_client};
+
+    #[tokio::test]
+    async fn referer_and_origin_are_sent_as_default_headers() {
+        let server = MockServer::start(vec![Reply::ok("ok")]).await;
+        let builder = CallixBuilder::new()
+            .referer("https://app.test/page")
+            .origin("https://app.test");
+        let callix = mock_client(builder, &server, EndpointConfig::new("GET", "/"));
+
+        callix
+            .request("mock", "call")
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+
+        let sent = &server.requests()[0];
+        assert_eq!(sent.header("referer"), Some("https://app.test/page"));
+        assert_eq!(sent.header("origin"), Some("https://app.test"));
+    }
+
+    #[test]
+    fn default_headers_are_replaced_case_insensitively() {
+        let builder = CallixBuilder::new()
+            .referer("https://old.test")
+            .default_header("Referer", "https://new.test");
+
+        assert_eq!(
+            builder.default_headers,
+            [("referer".to_string(), "https://new.test".to_string())]
+        );
+    }
+
+    #[test]
+    fn provider_headers_override_default_headers() {
+        let mut callix = CallixBuilder::new()
+            .origin("https://default.test")
+            .build()
+            .unwrap();
+        let provider = ProviderConfig::new("https://api.test")
+            .header("Origin", "https://provider.test")
+            .endpoint("ping", EndpointConfig::new("GET", "/ping"));
+        callix.register_provider("api", provider).unwrap();
+
+        let request = callix.request("api", "ping").unwrap();
+        let headers = request.build_request().unwrap().headers;
+        assert_eq!(
+            headers.get("origin").map(String::as_str),
+            Some("https://provider.test")
+        );
     }
 }
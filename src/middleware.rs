@@ -0,0 +1,100 @@
+use crate::error::Result;
+use reqwest::{Client, Request, Response};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+pub trait Middleware: Send + Sync {
+    fn handle<'a>(&'a self, request: Request, next: Next<'a>) -> BoxFuture<'a, Result<Response>>;
+}
+
+#[derive(Clone, Copy)]
+pub struct Next<'a> {
+    client: &'a Client,
+    middlewares: &'a [Arc<dyn Middleware>],
+}
+
+impl<'a> Next<'a> {
+    #[inline]
+    pub(crate) fn new(client: &'a Client, middlewares: &'a [Arc<dyn Middleware>]) -> Self {
+        Self {
+            client,
+            middlewares,
+        }
+    }
+
+    pub fn run(self, request: Request) -> BoxFuture<'a, Result<Response>> {
+        match self.middlewares.split_first() {
+            Some((middleware, rest)) => middleware.handle(request, Next::new(self.client, rest)),
+            None => Box::pin(async move { Ok(self.client.execute(request).await?) }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{MockServer, Reply};
+    use reqwest::header::HeaderValue;
+
+    struct InjectHeader(&'static str);
+
+    impl Middleware for InjectHeader {
+        fn handle<'a>(
+            &'a self,
+            mut request: Request,
+            next: Next<'a>,
+        ) -> BoxFuture<'a, Result<Response>> {
+            let trail = match request.headers().get("x-trail") {
+                Some(trail) => format!("{},{}", trail.to_str().unwrap(), self.0),
+                None => self.0.to_string(),
+            };
+            request
+                .headers_mut()
+                .insert("x-trail", HeaderValue::from_str(&trail).unwrap());
+            next.run(request)
+        }
+    }
+
+    struct ShortCircuit(String);
+
+    impl Middleware for ShortCircuit {
+        fn handle<'a>(&'a self, _: Request, _: Next<'a>) -> BoxFuture<'a, Result<Response>> {
+            Box::pin(async { Ok(reqwest::get(&self.0).await?) })
+        }
+    }
+
+    async fn run(middlewares: &[Arc<dyn Middleware>], server: &MockServer) -> Response {
+        let client = Client::new();
+        let request = client.get(&server.url).build().unwrap();
+        Next::new(&client, middlewares).run(request).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn middlewares_run_in_order_before_the_request() {
+        let server = MockServer::start(vec![Reply::ok("ok")]).await;
+        let middlewares: Vec<Arc<dyn Middleware>> =
+            vec![Arc::new(InjectHeader("a")), Arc::new(InjectHeader("b"))];
+
+        let response = run(&middlewares, &server).await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(server.requests()[0].header("x-trail"), Some("a,b"));
+    }
+
+    #[tokio::test]
+    async fn a_middleware_can_short_circuit_the_chain() {
+        let server = MockServer::start(vec![Reply::ok("ok")]).await;
+        let cache = MockServer::start(vec![Reply::new(418, &[], "cached")]).await;
+        let middlewares: Vec<Arc<dyn Middleware>> = vec![
+            Arc::new(ShortCircuit(cache.url.clone())),
+            Arc::new(InjectHeader("never")),
+        ];
+
+        let response = run(&middlewares, &server).await;
+        assert_eq!(response.status(), 418);
+        assert_eq!(response.text().await.unwrap(), "cached");
+        assert_eq!(server.hits(), 0);
+    }
+}
@@ -1,24 +1,180 @@
-use reqwest::Client;
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use futures_util::future::{Either, select};
+use futures_util::{Stream, stream};
+#[cfg(feature = "stream")]
+use futures_util::{TryStream, TryStreamExt};
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, utf8_percent_encode};
+use reqwest::header::{ACCEPT, ACCEPT_ENCODING, AUTHORIZATION, CONTENT_TYPE};
+use reqwest::{Client, Method, Url};
 use serde::Serialize;
-use serde_json::Value;
-use std::collections::HashMap;
-use std::time::Duration;
-use tokio::time::sleep;
+use serde_json::{Map, Number, Value};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::Path;
+use std::pin::pin;
+use std::sync::{Arc, OnceLock};
+#[cfg(feature = "stream")]
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::time::timeout;
+use uuid::Uuid;
 
-use crate::client::parse_method;
-use crate::config::{EndpointConfig, ProviderConfig};
+use crate::circuit::CircuitBreaker;
+use crate::client::is_idempotent;
+use crate::config::{
+    AuthConfig, BodyFormat, BodyType, Config, EndpointConfig, GraphqlConfig, ProviderConfig,
+};
 use crate::error::{CallixError, Result};
+use crate::middleware::{Middleware, Next};
 use crate::response::CallixResponse;
-use crate::template::TemplateEngine;
+use crate::retry::{RetryPolicy, rate_limit_delay, unix_seconds};
+use crate::source::VariableSource;
+use crate::template::{RenderOptions, TemplateEngine, TemplateTrace, encode_path_value};
+use crate::token_file::{TokenFiles, read_token};
+
+#[derive(Debug, Clone)]
+pub struct Explanation {
+    pub url: String,
+    pub body: Option<String>,
+    pub trace: TemplateTrace,
+}
+
+#[derive(Clone)]
+pub struct PreparedRequest {
+    pub method: Method,
+    pub url: Url,
+    pub headers: HashMap<String, String>,
+    pub body: Option<String>,
+    pub body_bytes: Option<Vec<u8>>,
+    secret_headers: Vec<String>,
+    secrets: Vec<String>,
+}
+
+impl fmt::Debug for PreparedRequest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let redact = |text: &str| redact_secrets(&self.secrets, text);
+        let headers: BTreeMap<&str, String> = self
+            .headers
+            .iter()
+            .map(|(key, value)| {
+                let value = if self.secret_headers.contains(key) {
+                    REDACTED.to_string()
+                } else {
+                    redact(value)
+                };
+                (key.as_str(), value)
+            })
+            .collect();
+
+        f.debug_struct("PreparedRequest")
+            .field("method", &self.method)
+            .field("url", &redact(self.url.as_str()))
+            .field("headers", &headers)
+            .field("body", &self.body.as_deref().map(redact))
+            .field(
+                "body_bytes",
+                &self.body_bytes.as_ref().map(|bytes| bytes.len()),
+            )
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RequestDiagnostics {
+    pub elapsed: Duration,
+    pub attempts: u32,
+    pub final_url: Url,
+}
+
+#[cfg(feature = "multipart")]
+struct MultipartUpload {
+    json_field: String,
+    file_field: String,
+    file_name: String,
+    content_type: String,
+    bytes: Vec<u8>,
+}
+
+#[cfg(feature = "multipart")]
+impl MultipartUpload {
+    fn form(&self, json: Option<String>) -> Result<reqwest::multipart::Form> {
+        use reqwest::multipart::{Form, Part};
+
+        let mut form = Form::new();
+        if let Some(json) = json {
+            let part = Part::text(json).mime_str("application/json")?;
+            form = form.part(self.json_field.clone(), part);
+        }
+
+        let file = Part::bytes(self.bytes.clone())
+            .file_name(self.file_name.clone())
+            .mime_str(&self.content_type)?;
+        Ok(form.part(self.file_field.clone(), file))
+    }
+}
+
+type Inspector<'a> = Box<dyn Fn(&PreparedRequest) + Send + Sync + 'a>;
+
+enum Target<'a> {
+    Borrowed {
+        provider: &'a ProviderConfig,
+        endpoint: &'a EndpointConfig,
+    },
+    Shared {
+        config: Arc<Config>,
+        provider: String,
+        endpoint: String,
+    },
+}
+
+const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+const TRACEPARENT_HEADER: &str = "traceparent";
+const SECRET_HEADERS: &[&str] = &["authorization", "proxy-authorization", "cookie"];
+const REDACTED: &str = "[REDACTED]";
+const MIN_REDACTED_LEN: usize = 6;
+const MERGED_VARIABLES: &str = "merge_vars_json";
+const QUERY_VALUE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'*')
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_');
 
 pub struct RequestBuilder<'a> {
     client: &'a Client,
-    provider_config: &'a ProviderConfig,
-    endpoint_config: &'a EndpointConfig,
+    target: Target<'a>,
     variables: HashMap<String, Value>,
-    max_retries: u32,
-    retry_delay: Duration,
+    invalid_variable: Option<(String, String)>,
+    invalid_trace_id: Option<String>,
+    secrets: Vec<String>,
+    raw_variables: Vec<String>,
+    var_sources: Vec<Arc<dyn VariableSource>>,
+    sourced_variables: OnceLock<HashMap<String, Value>>,
+    retry: RetryPolicy,
     custom_headers: HashMap<String, String>,
+    check_url_expiry: bool,
+    middlewares: &'a [Arc<dyn Middleware>],
+    render_options: RenderOptions,
+    request_id_header: Option<String>,
+    max_response_size: Option<usize>,
+    accept_encoding: Option<String>,
+    default_headers: &'a [(String, String)],
+    circuit: Option<&'a CircuitBreaker>,
+    hedge: Option<Duration>,
+    timeout_override: Option<Duration>,
+    replica: Option<usize>,
+    token_files: Option<&'a TokenFiles>,
+    success_statuses: Option<Vec<u16>>,
+    body_bytes: Option<Vec<u8>>,
+    inspector: Option<Inspector<'a>>,
+    next_url: Option<Url>,
+    #[cfg(feature = "multipart")]
+    multipart: Option<MultipartUpload>,
+    #[cfg(feature = "stream")]
+    body_stream: Mutex<Option<reqwest::Body>>,
+    #[cfg(feature = "tracing")]
+    trace_fields: &'a str,
 }
 
 impl<'a> RequestBuilder<'a> {
@@ -29,105 +185,3375 @@ impl<'a> RequestBuilder<'a> {
         max_retries: u32,
         retry_delay: Duration,
     ) -> Self {
+        let target = Target::Borrowed {
+            provider: provider_config,
+            endpoint: endpoint_config,
+        };
+        Self::with_target(client, target).retry_policy(RetryPolicy::new(max_retries, retry_delay))
+    }
+
+    pub(crate) fn shared(
+        client: &'a Client,
+        config: Arc<Config>,
+        provider: &str,
+        endpoint: &str,
+    ) -> Result<Self> {
+        config.get_provider(provider)?.get_endpoint(endpoint)?;
+
+        let target = Target::Shared {
+            config,
+            provider: provider.to_string(),
+            endpoint: endpoint.to_string(),
+        };
+        Ok(Self::with_target(client, target))
+    }
+
+    fn with_target(client: &'a Client, target: Target<'a>) -> Self {
         Self {
             client,
-            provider_config,
-            endpoint_config,
+            target,
             variables: HashMap::new(),
-            max_retries,
-            retry_delay,
+            invalid_variable: None,
+            invalid_trace_id: None,
+            secrets: Vec::new(),
+            raw_variables: Vec::new(),
+            var_sources: Vec::new(),
+            sourced_variables: OnceLock::new(),
+            retry: RetryPolicy::new(0, Duration::ZERO),
             custom_headers: HashMap::new(),
+            check_url_expiry: false,
+            middlewares: &[],
+            render_options: RenderOptions::default(),
+            request_id_header: None,
+            max_response_size: None,
+            accept_encoding: None,
+            default_headers: &[],
+            circuit: None,
+            hedge: None,
+            timeout_override: None,
+            replica: None,
+            token_files: None,
+            success_statuses: None,
+            body_bytes: None,
+            inspector: None,
+            next_url: None,
+            #[cfg(feature = "multipart")]
+            multipart: None,
+            #[cfg(feature = "stream")]
+            body_stream: Mutex::new(None),
+            #[cfg(feature = "tracing")]
+            trace_fields: "",
         }
     }
 
     pub fn var<T: Serialize>(mut self, key: impl Into<String>, value: T) -> Self {
         if let Ok(json_value) = serde_json::to_value(value) {
-            self.variables.insert(key.into(), json_value);
+            self.variables_mut().insert(key.into(), json_value);
+        }
+        self
+    }
+
+    pub fn var_secret<T: Serialize>(mut self, key: impl Into<String>, value: T) -> Self {
+        if let Ok(json_value) = serde_json::to_value(value) {
+            let secret = match &json_value {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            if !secret.is_empty() {
+                self.secrets.push(secret);
+            }
+            self.variables_mut().insert(key.into(), json_value);
+        }
+        self
+    }
+
+    pub fn var_raw(mut self, key: impl Into<String>, value: &str) -> Self {
+        let key = key.into();
+        self.variables_mut()
+            .insert(key.clone(), Value::String(value.to_string()));
+        if !self.raw_variables.contains(&key) {
+            self.raw_variables.push(key);
+        }
+        self
+    }
+
+    pub fn var_number(mut self, key: impl Into<String>, value: &str) -> Self {
+        let key = key.into();
+        let value = value.trim();
+        let number = value
+            .parse::<i64>()
+            .map(Number::from)
+            .ok()
+            .or_else(|| value.parse::<f64>().ok().and_then(Number::from_f64));
+
+        match number {
+            Some(number) => {
+                self.variables_mut().insert(key, Value::Number(number));
+            }
+            None => {
+                let reason = format!("expected a number, got '{}'", value);
+                self.invalid_variable.get_or_insert((key, reason));
+            }
+        }
+        self
+    }
+
+    pub fn var_bool(mut self, key: impl Into<String>, value: &str) -> Self {
+        let key = key.into();
+        let value = value.trim();
+
+        if value.eq_ignore_ascii_case("true") {
+            self.variables_mut().insert(key, Value::Bool(true));
+        } else if value.eq_ignore_ascii_case("false") {
+            self.variables_mut().insert(key, Value::Bool(false));
+        } else {
+            let reason = format!("expected true or false, got '{}'", value);
+            self.invalid_variable.get_or_insert((key, reason));
+        }
+        self
+    }
+
+    pub fn var_ns<T: Serialize>(
+        mut self,
+        namespace: impl Into<String>,
+        key: impl Into<String>,
+        value: T,
+    ) -> Self {
+        if let Ok(json_value) = serde_json::to_value(value) {
+            let entry = self
+                .variables_mut()
+                .entry(namespace.into())
+                .or_insert_with(|| Value::Object(Map::new()));
+
+            if !entry.is_object() {
+                *entry = Value::Object(Map::new());
+            }
+            if let Value::Object(map) = entry {
+                map.insert(key.into(), json_value);
+            }
         }
         self
     }
 
+    pub fn var_source(mut self, source: Arc<dyn VariableSource>) -> Self {
+        self.var_sources.push(source);
+        self.sourced_variables = OnceLock::new();
+        self
+    }
+
     pub fn vars(mut self, variables: HashMap<String, Value>) -> Self {
-        self.variables.extend(variables);
+        self.variables_mut().extend(variables);
+        self
+    }
+
+    pub fn merge_vars_json(mut self, json: &str) -> Self {
+        match serde_json::from_str(json) {
+            Ok(Value::Object(map)) => self.variables_mut().extend(map),
+            Ok(other) => {
+                let reason = format!("expected a JSON object, got {}", json_type_name(&other));
+                self.invalid_variable
+                    .get_or_insert((String::from(MERGED_VARIABLES), reason));
+            }
+            Err(e) => {
+                self.invalid_variable
+                    .get_or_insert((String::from(MERGED_VARIABLES), e.to_string()));
+            }
+        }
         self
     }
 
+    pub fn when(self, condition: bool, f: impl FnOnce(Self) -> Self) -> Self {
+        if condition { f(self) } else { self }
+    }
+
     pub fn header(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
         self.custom_headers.insert(key.into(), value.into());
         self
     }
 
+    pub fn content_type(self, mime: impl Into<String>) -> Self {
+        self.header(CONTENT_TYPE.as_str(), mime)
+    }
+
+    pub fn body_bytes(mut self, bytes: impl Into<Vec<u8>>) -> Self {
+        self.body_bytes = Some(bytes.into());
+        self
+    }
+
+    #[cfg(feature = "stream")]
+    pub fn body_stream<S>(mut self, stream: S) -> Self
+    where
+        S: TryStream + Send + 'static,
+        S::Ok: Into<Vec<u8>>,
+        S::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let body = reqwest::Body::wrap_stream(stream.map_ok(Into::<Vec<u8>>::into));
+        self.body_stream = Mutex::new(Some(body));
+        self
+    }
+
+    pub fn idempotency_key(self, key: impl Into<String>) -> Self {
+        self.header(IDEMPOTENCY_KEY_HEADER, key)
+    }
+
+    pub fn with_trace_id(mut self, trace_id: &str) -> Self {
+        let normalized = trace_id.to_ascii_lowercase();
+        let is_valid = normalized.len() == 32
+            && normalized.bytes().all(|b| b.is_ascii_hexdigit())
+            && normalized.bytes().any(|b| b != b'0');
+
+        if !is_valid {
+            self.invalid_trace_id.get_or_insert(trace_id.to_string());
+            return self;
+        }
+        self.traceparent(&normalized)
+    }
+
+    pub fn with_new_trace(self) -> Self {
+        let trace_id = Uuid::new_v4().simple().to_string();
+        self.traceparent(&trace_id)
+    }
+
+    fn traceparent(self, trace_id: &str) -> Self {
+        let span_id = &Uuid::new_v4().simple().to_string()[..16];
+        self.header(
+            TRACEPARENT_HEADER,
+            format!("00-{}-{}-01", trace_id, span_id),
+        )
+    }
+
+    pub fn retry_unsafe_methods(mut self, enabled: bool) -> Self {
+        self.retry.retry_unsafe_methods = enabled;
+        self
+    }
+
+    pub(crate) fn retry_policy(mut self, mut retry: RetryPolicy) -> Self {
+        if let Some(statuses) = &self.endpoint_config().retry_on_status {
+            retry.retry_statuses = statuses.clone();
+        }
+        self.retry = retry;
+        self
+    }
+
+    pub fn check_url_expiry(mut self, enabled: bool) -> Self {
+        self.check_url_expiry = enabled;
+        self
+    }
+
+    pub fn hedge(mut self, delay: Duration) -> Self {
+        self.hedge = Some(delay);
+        self
+    }
+
+    pub fn sort_keys(mut self, enabled: bool) -> Self {
+        self.render_options.sort_keys = enabled;
+        self
+    }
+
+    pub(crate) fn request_id_header(mut self, header: Option<String>) -> Self {
+        self.request_id_header = header;
+        self
+    }
+
+    pub(crate) fn max_response_size(mut self, limit: Option<usize>) -> Self {
+        self.max_response_size = limit;
+        self
+    }
+
+    pub(crate) fn accept_encoding(mut self, encoding: Option<String>) -> Self {
+        self.accept_encoding = encoding;
+        self
+    }
+
+    pub(crate) fn circuit(mut self, circuit: Option<&'a CircuitBreaker>) -> Self {
+        self.circuit = circuit;
+        self
+    }
+
+    pub(crate) fn timeout_override(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout_override = timeout;
+        self
+    }
+
+    pub(crate) fn replica(mut self, replica: Option<usize>) -> Self {
+        self.replica = replica;
+        self
+    }
+
+    pub(crate) fn token_files(mut self, token_files: &'a TokenFiles) -> Self {
+        self.token_files = Some(token_files);
+        self
+    }
+
+    pub(crate) fn default_headers(mut self, headers: &'a [(String, String)]) -> Self {
+        self.default_headers = headers;
+        self
+    }
+
+    pub(crate) fn success_statuses(mut self, statuses: Option<Vec<u16>>) -> Self {
+        self.success_statuses = statuses;
+        self
+    }
+
+    #[cfg(feature = "tracing")]
+    pub(crate) fn trace_fields(mut self, fields: &'a str) -> Self {
+        self.trace_fields = fields;
+        self
+    }
+
+    pub(crate) fn middlewares(mut self, middlewares: &'a [Arc<dyn Middleware>]) -> Self {
+        self.middlewares = middlewares;
+        self
+    }
+
+    pub fn inspect(mut self, inspector: impl Fn(&PreparedRequest) + Send + Sync + 'a) -> Self {
+        self.inspector = Some(Box::new(inspector));
+        self
+    }
+
+    #[cfg(feature = "multipart")]
+    pub fn multipart_json_and_file(
+        mut self,
+        json_field: impl Into<String>,
+        file_field: impl Into<String>,
+        file_name: impl Into<String>,
+        bytes: impl Into<Vec<u8>>,
+        content_type: impl Into<String>,
+    ) -> Self {
+        self.multipart = Some(MultipartUpload {
+            json_field: json_field.into(),
+            file_field: file_field.into(),
+            file_name: file_name.into(),
+            content_type: content_type.into(),
+            bytes: bytes.into(),
+        });
+        self
+    }
+
+    pub fn build_request(&self) -> Result<PreparedRequest> {
+        self.check_variables()?;
+        let url = self.build_url()?;
+        let method = self.endpoint_config().method()?;
+
+        let mut headers = self.build_headers()?;
+        if self.is_multipart() {
+            headers.remove(CONTENT_TYPE.as_str());
+        } else if self.body_bytes.is_some() || self.has_body_stream() {
+            headers
+                .entry(CONTENT_TYPE.to_string())
+                .or_insert_with(|| String::from("application/octet-stream"));
+        } else if self.body_type() == BodyType::Form {
+            if self.endpoint_config().body.is_some()
+                || self.endpoint_config().body_template.is_some()
+            {
+                headers
+                    .entry(CONTENT_TYPE.to_string())
+                    .or_insert_with(|| String::from("application/x-www-form-urlencoded"));
+            }
+        } else if self.endpoint_config().body.is_some()
+            || self.endpoint_config().graphql.is_some()
+            || self.endpoint_config().body_format != BodyFormat::Json
+        {
+            headers
+                .entry(CONTENT_TYPE.to_string())
+                .or_insert_with(|| String::from("application/json"));
+        }
+
+        let body = match self.body_bytes {
+            Some(_) => None,
+            None => self.render_body()?,
+        };
+        let body = match body {
+            Some(body) if self.body_type() == BodyType::Form && !self.is_multipart() => {
+                Some(form_encode(&body)?)
+            }
+            body => body,
+        };
+
+        let secret_headers = headers
+            .keys()
+            .filter(|key| self.is_secret_header(key))
+            .cloned()
+            .collect();
+        Ok(PreparedRequest {
+            method,
+            url,
+            headers,
+            body,
+            body_bytes: self.body_bytes.clone(),
+            secret_headers,
+            secrets: self.secrets.clone(),
+        })
+    }
+
+    pub fn dry_run_curl(&self, redact: bool) -> Result<String> {
+        let prepared = self.build_request()?;
+        let mut parts = vec![format!(
+            "curl -X {} {}",
+            prepared.method,
+            shell_quote(prepared.url.as_str())
+        )];
+
+        let mut headers: Vec<_> = prepared.headers.iter().collect();
+        headers.sort();
+        for (key, value) in headers {
+            let value = if redact && self.is_secret_header(key) {
+                REDACTED
+            } else {
+                value
+            };
+            parts.push(format!(
+                "-H {}",
+                shell_quote(&format!("{}: {}", key, value))
+            ));
+        }
+
+        if let Some(body) = &prepared.body {
+            parts.push(format!("--data-raw {}", shell_quote(body)));
+        } else if let Some(bytes) = &prepared.body_bytes {
+            parts.push(format!(
+                "--data-binary {}",
+                shell_quote(&String::from_utf8_lossy(bytes))
+            ));
+        }
+
+        Ok(self.redact_secrets(&parts.join(" \\\n  ")))
+    }
+
+    pub fn preview_body(&self) -> Result<String> {
+        self.check_variables()?;
+        Ok(self.render_body()?.unwrap_or_default())
+    }
+
+    pub fn explain(&self) -> Result<Explanation> {
+        self.check_variables()?;
+        let mut trace = TemplateTrace::new();
+        let variables = self.variables();
+
+        for source in self.template_sources() {
+            let (_, steps) = TemplateEngine::render_traced(source, &variables)?;
+            trace.extend(
+                steps
+                    .into_iter()
+                    .map(|(name, value)| (name, self.redact_secrets(&value))),
+            );
+        }
+
+        Ok(Explanation {
+            url: self.redact_secrets(self.build_url()?.as_str()),
+            body: self.render_body()?.map(|body| self.redact_secrets(&body)),
+            trace,
+        })
+    }
+
+    fn template_sources(&self) -> Vec<&str> {
+        let (base_url, _) = self.base_url();
+        let mut sources = vec![base_url, self.endpoint_config().path.as_str()];
+        sources.extend(
+            self.provider_config()
+                .default_query_params
+                .values()
+                .map(String::as_str),
+        );
+        sources.extend(
+            self.endpoint_config()
+                .query_params
+                .values()
+                .map(String::as_str),
+        );
+        sources.extend(self.endpoint_config().body_template.as_deref());
+
+        if let Some(body) = &self.endpoint_config().body {
+            collect_strings(body, &mut sources);
+        }
+        if let Some(graphql) = &self.endpoint_config().graphql {
+            sources.push(graphql.query.as_str());
+            if let Some(variables) = &graphql.variables {
+                collect_strings(variables, &mut sources);
+            }
+        }
+        sources
+    }
+
+    fn header_sources(&self) -> Vec<&str> {
+        let mut sources: Vec<&str> = self
+            .provider_config()
+            .headers
+            .values()
+            .chain(self.endpoint_config().headers.values())
+            .map(String::as_str)
+            .collect();
+
+        let auths = [
+            self.endpoint_config().auth.as_ref(),
+            self.provider_config().auth.as_ref(),
+        ];
+        for auth in auths.into_iter().flatten() {
+            match auth {
+                AuthConfig::Bearer { token } => sources.push(token),
+                AuthConfig::Basic { username, password } => {
+                    sources.push(username);
+                    sources.extend(password.as_deref());
+                }
+                AuthConfig::Header { value, .. } => sources.push(value),
+                AuthConfig::BearerFile { .. } | AuthConfig::None => {}
+            }
+        }
+        sources
+    }
+
+    fn variables(&self) -> Cow<'_, HashMap<String, Value>> {
+        let sourced = self.sourced_variables();
+        if sourced.is_empty() {
+            return Cow::Borrowed(&self.variables);
+        }
+
+        let mut variables = sourced.clone();
+        variables.extend(
+            self.variables
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone())),
+        );
+        Cow::Owned(variables)
+    }
+
+    fn variables_mut(&mut self) -> &mut HashMap<String, Value> {
+        self.sourced_variables = OnceLock::new();
+        &mut self.variables
+    }
+
+    fn sourced_variables(&self) -> &HashMap<String, Value> {
+        self.sourced_variables.get_or_init(|| {
+            let mut sourced = HashMap::new();
+            if self.var_sources.is_empty() {
+                return sourced;
+            }
+
+            let sources = self
+                .template_sources()
+                .into_iter()
+                .chain(self.header_sources());
+            for template in sources {
+                for name in TemplateEngine::extract_variables(template) {
+                    if TemplateEngine::lookup(&self.variables, &name).is_some()
+                        || TemplateEngine::lookup(&sourced, &name).is_some()
+                    {
+                        continue;
+                    }
+
+                    let namespace = name.split_once('.').map(|(namespace, _)| namespace);
+                    let found = self
+                        .fetch_source(&name)
+                        .map(|value| (name.clone(), value))
+                        .or_else(|| {
+                            namespace
+                                .and_then(|key| Some((key.to_string(), self.fetch_source(key)?)))
+                        });
+                    if let Some((key, value)) = found {
+                        sourced.insert(key, value);
+                    }
+                }
+            }
+            sourced
+        })
+    }
+
+    fn fetch_source(&self, key: &str) -> Option<Value> {
+        self.var_sources.iter().find_map(|source| source.get(key))
+    }
+
     pub async fn send(self) -> Result<CallixResponse> {
-        let mut last_error = None;
-
-        for attempt in 0..=self.max_retries {
-            match self.execute_request().await {
-                Ok(response) => return Ok(response),
-                Err(e) if attempt < self.max_retries => {
-                    last_error = Some(e);
-                    sleep(self.retry_delay).await;
+        self.send_all_retries_errors().await.map_err(last_error)
+    }
+
+    pub async fn send_all_retries_errors(
+        mut self,
+    ) -> std::result::Result<CallixResponse, Vec<CallixError>> {
+        let request_id = self.ensure_request_id();
+        let (response, _) = self.send_with_retries().await?;
+        Ok(self.finish(response, request_id))
+    }
+
+    pub async fn send_with_diagnostics(mut self) -> Result<(CallixResponse, RequestDiagnostics)> {
+        let started = Instant::now();
+        let request_id = self.ensure_request_id();
+        let (response, attempts) = self.send_with_retries().await.map_err(last_error)?;
+        let response = self.finish(response, request_id);
+
+        let diagnostics = RequestDiagnostics {
+            elapsed: started.elapsed(),
+            attempts,
+            final_url: response.url().clone(),
+        };
+        Ok((response, diagnostics))
+    }
+
+    pub fn paginate(self) -> impl Stream<Item = Result<CallixResponse>> + 'a {
+        stream::unfold(Some(self), |state| async move {
+            let mut request = state?;
+            let request_id = request.ensure_request_id();
+            match request.send_with_retries().await {
+                Ok((response, _)) => {
+                    let response = request.finish(response, request_id);
+                    let next = response.next_link().map(|url| {
+                        request.next_url = Some(url);
+                        request
+                    });
+                    Some((Ok(response), next))
                 }
-                Err(e) => return Err(e),
+                Err(errors) => Some((Err(last_error(errors)), None)),
             }
+        })
+    }
+
+    fn finish(&self, response: CallixResponse, request_id: Option<String>) -> CallixResponse {
+        response
+            .with_request_id(request_id)
+            .with_max_size(self.max_response_size)
+            .with_success_statuses(self.success_statuses.clone())
+    }
+
+    #[cfg(feature = "tracing")]
+    async fn send_with_retries(
+        &self,
+    ) -> std::result::Result<(CallixResponse, u32), Vec<CallixError>> {
+        use tracing::Instrument;
+        let result = self.retry_loop().instrument(self.span()).await;
+        self.record_circuit(&result);
+        result
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    async fn send_with_retries(
+        &self,
+    ) -> std::result::Result<(CallixResponse, u32), Vec<CallixError>> {
+        let result = self.retry_loop().await;
+        self.record_circuit(&result);
+        result
+    }
+
+    fn allow_circuit(&self) -> Result<()> {
+        match (self.circuit, &self.target) {
+            (Some(circuit), Target::Shared { provider, .. }) => circuit.allow(provider),
+            _ => Ok(()),
         }
+    }
 
-        Err(last_error.unwrap_or(CallixError::MaxRetriesExceeded))
+    fn record_circuit(
+        &self,
+        result: &std::result::Result<(CallixResponse, u32), Vec<CallixError>>,
+    ) {
+        let (Some(circuit), Target::Shared { provider, .. }) = (self.circuit, &self.target) else {
+            return;
+        };
+
+        let success = match result {
+            Ok((response, _)) => response.status() < 500,
+            Err(errors) if errors.last().is_some_and(CallixError::is_retriable) => false,
+            Err(_) => return,
+        };
+        circuit.record(provider, success);
     }
 
-    async fn execute_request(&self) -> Result<CallixResponse> {
-        let url = self.build_url()?;
-        let method = parse_method(&self.endpoint_config.method)?;
+    #[cfg(feature = "tracing")]
+    fn span(&self) -> tracing::Span {
+        let (provider, endpoint) = match &self.target {
+            Target::Borrowed { .. } => (None, None),
+            Target::Shared {
+                provider, endpoint, ..
+            } => (Some(provider.as_str()), Some(endpoint.as_str())),
+        };
+
+        tracing::info_span!(
+            "callix.request",
+            provider,
+            endpoint,
+            method = self.endpoint_config().method.as_str(),
+            fields = self.trace_fields,
+        )
+    }
+
+    async fn retry_loop(&self) -> std::result::Result<(CallixResponse, u32), Vec<CallixError>> {
+        let mut errors = Vec::new();
+        let can_retry = self.can_retry();
+        let endpoint = self.endpoint_config();
+        let retries_status =
+            can_retry || (endpoint.retry_on_status.is_some() && !self.has_body_stream());
+        let retries_body = endpoint.retry_on_body.is_some() && !self.has_body_stream();
+        let attempts = if retries_status || retries_body {
+            self.retry.attempts()
+        } else {
+            1
+        };
+
+        let deadline = self
+            .retry
+            .total_deadline
+            .map(|limit| Instant::now() + limit);
+
+        for attempt in 1..=attempts {
+            if let Err(e) = self.allow_circuit() {
+                errors.push(e);
+                break;
+            }
+
+            let result = match deadline {
+                Some(deadline) => timeout(
+                    deadline.saturating_duration_since(Instant::now()),
+                    self.execute_hedged(),
+                )
+                .await
+                .unwrap_or(Err(CallixError::TimeoutError)),
+                None => self.execute_hedged().await,
+            };
+
+            match result {
+                Ok(response)
+                    if retries_status
+                        && attempt < attempts
+                        && self.retry.retries_status(response.status()) =>
+                {
+                    let delay = match rate_limit_delay(response.headers()) {
+                        Some(delay) => self.retry.clamp_delay(delay),
+                        None => self.retry.delay_for(attempt, None),
+                    };
+                    if past_deadline(deadline, delay) {
+                        errors.push(CallixError::TimeoutError);
+                        break;
+                    }
+                    self.retry.sleeper.sleep(delay).await;
+                }
+                Ok(response) if retries_body && attempt < attempts => {
+                    let (response, body) = match response
+                        .with_max_size(self.max_response_size)
+                        .buffer()
+                        .await
+                    {
+                        Ok(buffered) => buffered,
+                        Err(e) => {
+                            errors.push(e);
+                            break;
+                        }
+                    };
+                    let retry = self
+                        .endpoint_config()
+                        .retry_on_body
+                        .as_ref()
+                        .is_some_and(|rule| rule.matches(&body));
+                    if !retry {
+                        return Ok((response, attempt));
+                    }
+
+                    let delay = self.retry.delay_for(attempt, None);
+                    if past_deadline(deadline, delay) {
+                        errors.push(CallixError::TimeoutError);
+                        break;
+                    }
+                    self.retry.sleeper.sleep(delay).await;
+                }
+                Ok(response) => return Ok((response, attempt)),
+                Err(e) => {
+                    if !can_retry || attempt == attempts || !e.is_retriable() {
+                        errors.push(e);
+                        break;
+                    }
 
-        let mut request = self.client.request(method, &url);
+                    let delay = self.retry.delay_for(attempt, Some(&e));
+                    let timed_out = matches!(e, CallixError::TimeoutError);
+                    errors.push(e);
+                    if past_deadline(deadline, delay) {
+                        if !timed_out {
+                            errors.push(CallixError::TimeoutError);
+                        }
+                        break;
+                    }
+                    self.retry.sleeper.sleep(delay).await;
+                }
+            }
+        }
+
+        Err(errors.into_iter().map(|e| self.redact_error(e)).collect())
+    }
+
+    fn redact_secrets(&self, text: &str) -> String {
+        redact_secrets(&self.secrets, text)
+    }
 
-        for (key, value) in &self.provider_config.headers {
-            let rendered = TemplateEngine::render(value, &self.variables)?;
-            request = request.header(key, rendered.as_ref());
+    fn redact_error(&self, error: CallixError) -> CallixError {
+        let redact = |text: String| self.redact_secrets(&text);
+        match error {
+            CallixError::HttpError(e)
+                if e.url()
+                    .is_some_and(|url| self.redact_secrets(url.as_str()) != url.as_str()) =>
+            {
+                CallixError::HttpError(e.without_url())
+            }
+            CallixError::EndpointNotFound(name) => CallixError::EndpointNotFound(redact(name)),
+            CallixError::UnknownConfigField(field) => {
+                CallixError::UnknownConfigField(redact(field))
+            }
+            CallixError::ApiError { status, body } => CallixError::ApiError {
+                status,
+                body: redact(body),
+            },
+            CallixError::InvalidUrl(url) => CallixError::InvalidUrl(redact(url)),
+            CallixError::JsonPointerNotFound(pointer) => {
+                CallixError::JsonPointerNotFound(redact(pointer))
+            }
+            CallixError::ValidationError(message) => CallixError::ValidationError(redact(message)),
+            CallixError::IoError(e) => {
+                let message = e.to_string();
+                let redacted = self.redact_secrets(&message);
+                if redacted == message {
+                    CallixError::IoError(e)
+                } else {
+                    CallixError::IoError(std::io::Error::new(e.kind(), redacted))
+                }
+            }
+            CallixError::InvalidVariable { name, reason } => CallixError::InvalidVariable {
+                name: redact(name),
+                reason: redact(reason),
+            },
+            CallixError::InvalidBody(message) => CallixError::InvalidBody(redact(message)),
+            CallixError::UnexpectedContentType { expected, actual } => {
+                CallixError::UnexpectedContentType {
+                    expected: redact(expected),
+                    actual: actual.map(redact),
+                }
+            }
+            CallixError::CircuitOpen(provider) => CallixError::CircuitOpen(redact(provider)),
+            other => other,
         }
+    }
 
-        for (key, value) in &self.custom_headers {
-            request = request.header(key, value);
+    pub async fn expect_status(self, allowed: &[u16]) -> Result<CallixResponse> {
+        let response = self.send().await?;
+        let status = response.status();
+
+        if allowed.contains(&status) {
+            return Ok(response);
         }
 
-        if let Some(body_template) = &self.endpoint_config.body_template {
-            let body = TemplateEngine::render(body_template, &self.variables)?;
-            request = request.body(body.into_owned());
+        let body = response.error_body().await;
+        Err(CallixError::ApiError { status, body })
+    }
+
+    fn ensure_request_id(&mut self) -> Option<String> {
+        let header = self.request_id_header.as_deref()?;
+
+        if let Some((_, existing)) = self
+            .custom_headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(header))
+        {
+            return Some(existing.clone());
         }
 
-        let response = request.send().await?;
-        Ok(CallixResponse::new(response))
+        let request_id = Uuid::new_v4().to_string();
+        self.custom_headers
+            .insert(header.to_string(), request_id.clone());
+        Some(request_id)
+    }
+
+    fn provider_config(&self) -> &ProviderConfig {
+        match &self.target {
+            Target::Borrowed { provider, .. } => provider,
+            Target::Shared {
+                config, provider, ..
+            } => &config.providers[provider],
+        }
     }
 
-    fn build_url(&self) -> Result<String> {
-        let path = TemplateEngine::render(&self.endpoint_config.path, &self.variables)?;
-        let base_len = self.provider_config.base_url.len();
-        let path_len = path.len();
+    fn endpoint_config(&self) -> &EndpointConfig {
+        match &self.target {
+            Target::Borrowed { endpoint, .. } => endpoint,
+            Target::Shared {
+                config,
+                provider,
+                endpoint,
+            } => &config.providers[provider].endpoints[endpoint],
+        }
+    }
 
-        if self.endpoint_config.query_params.is_empty() {
-            let mut url = String::with_capacity(base_len + path_len);
-            url.push_str(&self.provider_config.base_url);
-            url.push_str(&path);
-            return Ok(url);
+    fn is_secret_header(&self, name: &str) -> bool {
+        let auth = self
+            .endpoint_config()
+            .auth
+            .as_ref()
+            .or(self.provider_config().auth.as_ref());
+        if auth.is_some_and(|auth| auth.overrides_header(name)) {
+            return true;
         }
 
-        let mut url = String::with_capacity(base_len + path_len + 128);
-        url.push_str(&self.provider_config.base_url);
-        url.push_str(&path);
-        url.push('?');
+        let name = name.to_ascii_lowercase();
+        SECRET_HEADERS.contains(&name.as_str())
+            || ["key", "token", "secret"]
+                .iter()
+                .any(|marker| name.contains(marker))
+    }
 
-        let mut first = true;
-        for (k, v) in &self.endpoint_config.query_params {
-            if !first {
-                url.push('&');
-            }
-            first = false;
-            url.push_str(k);
-            url.push('=');
-            let value = TemplateEngine::render(v, &self.variables)
-                .unwrap_or_else(|_| std::borrow::Cow::Borrowed(v));
-            url.push_str(&value);
+    fn check_variables(&self) -> Result<()> {
+        if let Some((name, reason)) = &self.invalid_variable {
+            return Err(CallixError::InvalidVariable {
+                name: name.clone(),
+                reason: reason.clone(),
+            });
         }
+        match &self.invalid_trace_id {
+            Some(trace_id) => Err(CallixError::ValidationError(format!(
+                "invalid trace id '{}': expected 32 hex digits, not all zero",
+                trace_id
+            ))),
+            None => Ok(()),
+        }
+    }
 
-        Ok(url)
+    fn can_retry(&self) -> bool {
+        if self.has_body_stream() {
+            return false;
+        }
+        if self.retry.retry_unsafe_methods {
+            return true;
+        }
+
+        let has_idempotency_key = self
+            .custom_headers
+            .keys()
+            .any(|key| key.eq_ignore_ascii_case(IDEMPOTENCY_KEY_HEADER));
+
+        has_idempotency_key
+            || self
+                .endpoint_config()
+                .method()
+                .is_ok_and(|m| is_idempotent(&m))
+    }
+
+    async fn execute_hedged(&self) -> Result<CallixResponse> {
+        let hedge = self.hedge.filter(|_| {
+            !self.has_body_stream()
+                && self
+                    .endpoint_config()
+                    .method()
+                    .is_ok_and(|m| is_idempotent(&m))
+        });
+        let Some(delay) = hedge else {
+            return self.execute_request().await;
+        };
+
+        let mut primary = pin!(self.execute_request());
+        if let Ok(result) = timeout(delay, &mut primary).await {
+            return result;
+        }
+
+        let secondary = pin!(self.execute_request());
+        match select(primary, secondary).await {
+            Either::Left((Ok(response), _)) | Either::Right((Ok(response), _)) => Ok(response),
+            Either::Left((Err(_), secondary)) => secondary.await,
+            Either::Right((Err(_), primary)) => primary.await,
+        }
+    }
+
+    async fn execute_request(&self) -> Result<CallixResponse> {
+        let prepared = self.build_request()?;
+        if let Some(inspector) = &self.inspector {
+            inspector(&prepared);
+        }
+
+        let mut request = self.client.request(prepared.method, prepared.url);
+        if let Some(timeout) = self.request_timeout() {
+            request = request.timeout(timeout);
+        }
+        for (key, value) in prepared.headers {
+            request = request.header(key, value);
+        }
+
+        let request = match (prepared.body_bytes, self.take_body_stream()) {
+            (Some(bytes), _) => request.body(bytes),
+            (None, Some(stream)) => request.body(stream),
+            (None, None) => self.attach_body(request, prepared.body)?,
+        };
+        let request = request.build()?;
+        let started = Instant::now();
+        let response = Next::new(self.client, self.middlewares)
+            .run(request)
+            .await
+            .map_err(|e| match e {
+                CallixError::HttpError(e) if e.is_timeout() => CallixError::TimeoutError,
+                e => e,
+            })?;
+        Ok(CallixResponse::new(response).with_elapsed(started.elapsed()))
+    }
+
+    fn request_timeout(&self) -> Option<Duration> {
+        if self.timeout_override.is_some() {
+            return self.timeout_override;
+        }
+        match self.endpoint_config().timeout_ms {
+            Some(millis) => Some(Duration::from_millis(millis)),
+            None => self.provider_config().timeout.map(Duration::from_secs),
+        }
+    }
+
+    fn body_type(&self) -> BodyType {
+        self.endpoint_config()
+            .body_type
+            .or(self.provider_config().default_body_type)
+            .unwrap_or_default()
+    }
+
+    #[cfg(feature = "stream")]
+    fn has_body_stream(&self) -> bool {
+        self.body_stream
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_some()
+    }
+
+    #[cfg(not(feature = "stream"))]
+    fn has_body_stream(&self) -> bool {
+        false
+    }
+
+    #[cfg(feature = "stream")]
+    fn take_body_stream(&self) -> Option<reqwest::Body> {
+        self.body_stream
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+    }
+
+    #[cfg(not(feature = "stream"))]
+    fn take_body_stream(&self) -> Option<reqwest::Body> {
+        None
+    }
+
+    #[cfg(feature = "multipart")]
+    fn is_multipart(&self) -> bool {
+        self.multipart.is_some()
+    }
+
+    #[cfg(not(feature = "multipart"))]
+    fn is_multipart(&self) -> bool {
+        false
+    }
+
+    #[cfg(feature = "multipart")]
+    fn attach_body(
+        &self,
+        request: reqwest::RequestBuilder,
+        body: Option<String>,
+    ) -> Result<reqwest::RequestBuilder> {
+        Ok(match (&self.multipart, body) {
+            (Some(upload), body) => request.multipart(upload.form(body)?),
+            (None, Some(body)) => request.body(body),
+            (None, None) => request,
+        })
+    }
+
+    #[cfg(not(feature = "multipart"))]
+    fn attach_body(
+        &self,
+        request: reqwest::RequestBuilder,
+        body: Option<String>,
+    ) -> Result<reqwest::RequestBuilder> {
+        Ok(match body {
+            Some(body) => request.body(body),
+            None => request,
+        })
+    }
+
+    fn build_headers(&self) -> Result<HashMap<String, String>> {
+        let mut headers = HashMap::new();
+        if let Some(encoding) = &self.accept_encoding {
+            headers.insert(ACCEPT_ENCODING.to_string(), encoding.clone());
+        }
+        for (key, value) in self.default_headers {
+            headers.insert(key.clone(), value.clone());
+        }
+        let endpoint_auth = self.endpoint_config().auth.as_ref();
+
+        for (key, value) in &self.provider_config().headers {
+            if endpoint_auth.is_some_and(|auth| auth.overrides_header(key)) {
+                continue;
+            }
+            headers.insert(key.to_ascii_lowercase(), self.render(value)?.into_owned());
+        }
+
+        for (key, value) in &self.endpoint_config().headers {
+            headers.insert(key.to_ascii_lowercase(), self.render(value)?.into_owned());
+        }
+
+        if let Some(accept) = &self.endpoint_config().accept {
+            headers.insert(ACCEPT.to_string(), accept.clone());
+        }
+
+        match endpoint_auth.or(self.provider_config().auth.as_ref()) {
+            Some(AuthConfig::Bearer { token }) => {
+                let token = self.render(token)?;
+                headers.insert(AUTHORIZATION.to_string(), format!("Bearer {}", token));
+            }
+            Some(AuthConfig::BearerFile { path }) => {
+                let token = match self.token_files {
+                    Some(token_files) => token_files.read(Path::new(path))?,
+                    None => read_token(Path::new(path))?,
+                };
+                headers.insert(AUTHORIZATION.to_string(), format!("Bearer {}", token));
+            }
+            Some(AuthConfig::Basic { username, password }) => {
+                let mut credentials = self.render(username)?.into_owned();
+                credentials.push(':');
+                if let Some(password) = password {
+                    credentials.push_str(&self.render(password)?);
+                }
+                let encoded = BASE64_STANDARD.encode(credentials);
+                headers.insert(AUTHORIZATION.to_string(), format!("Basic {}", encoded));
+            }
+            Some(AuthConfig::Header { name, value }) => {
+                headers.insert(name.to_ascii_lowercase(), self.render(value)?.into_owned());
+            }
+            Some(AuthConfig::None) | None => {}
+        }
+
+        for (key, value) in &self.custom_headers {
+            headers.insert(key.to_ascii_lowercase(), value.clone());
+        }
+
+        Ok(headers)
+    }
+
+    fn render_body(&self) -> Result<Option<String>> {
+        if let Some(graphql) = &self.endpoint_config().graphql {
+            return self.render_graphql(graphql).map(Some);
+        }
+
+        let body = match (
+            &self.endpoint_config().body,
+            &self.endpoint_config().body_template,
+        ) {
+            (Some(body), _) => TemplateEngine::render_value_with(
+                body,
+                &*self.structured_variables()?,
+                self.render_options,
+            )?,
+            (None, Some(body_template)) => {
+                let body = self.render(body_template)?;
+                match self.endpoint_config().body_format {
+                    BodyFormat::Json if self.provider_config().default_body.is_none() => {
+                        return Ok(Some(body.into_owned()));
+                    }
+                    BodyFormat::Json => {
+                        serde_json::from_str(&body).map_err(CallixError::JsonError)?
+                    }
+                    BodyFormat::Yaml => serde_yaml::from_str(&body)
+                        .map_err(|e| CallixError::InvalidBody(e.to_string()))?,
+                    #[cfg(feature = "toml")]
+                    BodyFormat::Toml => toml::from_str(&body)
+                        .map_err(|e| CallixError::InvalidBody(e.to_string()))?,
+                }
+            }
+            (None, None) => return Ok(None),
+        };
+
+        let mut body = match &self.provider_config().default_body {
+            Some(default_body) => {
+                let mut merged = default_body.clone();
+                merge_json(&mut merged, body);
+                merged
+            }
+            None => body,
+        };
+
+        if self.render_options.sort_keys {
+            body = TemplateEngine::sort_keys(&body);
+        }
+
+        Ok(Some(body.to_string()))
+    }
+
+    fn structured_variables(&self) -> Result<Cow<'_, HashMap<String, Value>>> {
+        let variables = self.variables();
+        if self.raw_variables.is_empty() {
+            return Ok(variables);
+        }
+
+        let mut variables = variables.into_owned();
+        for key in &self.raw_variables {
+            if let Some(Value::String(raw)) = variables.get(key) {
+                let value = serde_json::from_str(raw).map_err(|e| {
+                    CallixError::InvalidBody(format!("raw variable '{}': {}", key, e))
+                })?;
+                variables.insert(key.clone(), value);
+            }
+        }
+        Ok(Cow::Owned(variables))
+    }
+
+    fn render_graphql(&self, graphql: &GraphqlConfig) -> Result<String> {
+        let mut envelope = Map::new();
+        envelope.insert(
+            String::from("query"),
+            Value::String(self.render(&graphql.query)?.into_owned()),
+        );
+        if let Some(template) = &graphql.variables {
+            let variables = TemplateEngine::render_value_with(
+                template,
+                &*self.structured_variables()?,
+                self.render_options,
+            )?;
+            envelope.insert(String::from("variables"), variables);
+        }
+
+        let mut body = Value::Object(envelope);
+        if self.render_options.sort_keys {
+            body = TemplateEngine::sort_keys(&body);
+        }
+        Ok(body.to_string())
+    }
+
+    #[inline]
+    fn render<'t>(&self, template: &'t str) -> Result<Cow<'t, str>> {
+        TemplateEngine::render_with(template, &self.variables(), self.render_options)
+    }
+
+    fn base_url(&self) -> (&str, Option<&Url>) {
+        let provider = self.provider_config();
+        let replica = match self.replica {
+            Some(index) => Some(index),
+            None if provider.base_url.is_empty() && !provider.base_urls.is_empty() => Some(0),
+            None => None,
+        };
+
+        match replica.and_then(|index| Some((provider.base_urls.get(index)?, index))) {
+            Some((replica, index)) => (
+                replica.url(),
+                provider
+                    .parsed_base_urls
+                    .get(index)
+                    .and_then(Option::as_ref),
+            ),
+            None => (&provider.base_url, provider.parsed_base_url.as_ref()),
+        }
+    }
+
+    fn build_url(&self) -> Result<Url> {
+        if let Some(url) = &self.next_url {
+            return Ok(url.clone());
+        }
+
+        let template = &self.endpoint_config().path;
+        let mut path = self.render(template)?;
+        if !is_absolute_url(&path) {
+            let options = RenderOptions {
+                encode_path: true,
+                ..self.render_options
+            };
+            path = TemplateEngine::render_with(template, &self.variables(), options)?;
+        }
+        let base = match self.base_url() {
+            (_, Some(base)) => Cow::Borrowed(base),
+            (base_url, None) => Cow::Owned(parse_url(&self.render(base_url)?)?),
+        };
+
+        let mut url = join_url(&base, &path)?;
+
+        let endpoint_params = &self.endpoint_config().query_params;
+        let provider_params = self
+            .provider_config()
+            .default_query_params
+            .iter()
+            .filter(|(k, _)| !endpoint_params.contains_key(*k));
+
+        if !endpoint_params.is_empty() || !self.provider_config().default_query_params.is_empty() {
+            let mut pairs = url.query_pairs_mut();
+            for (k, v) in provider_params.chain(endpoint_params) {
+                if let Some(values) =
+                    TemplateEngine::render_list(v, &self.variables(), self.render_options)
+                {
+                    for value in values? {
+                        pairs.append_pair(k, &value);
+                    }
+                    continue;
+                }
+                let value = self.render(v).unwrap_or_else(|_| Cow::Borrowed(v));
+                pairs.append_pair(k, &value);
+            }
+        }
+
+        if self.check_url_expiry && is_signature_expired(&url) {
+            return Err(CallixError::UrlExpired);
+        }
+
+        Ok(url)
+    }
+}
+
+fn redact_secrets(secrets: &[String], text: &str) -> String {
+    let mut text = text.to_string();
+    for secret in secrets
+        .iter()
+        .filter(|secret| secret.chars().count() >= MIN_REDACTED_LEN)
+    {
+        for form in secret_forms(secret) {
+            text = text.replace(&form, REDACTED);
+        }
+    }
+    text
+}
+
+fn secret_forms(secret: &str) -> Vec<String> {
+    let json = serde_json::to_string(secret).unwrap_or_default();
+    let mut forms = vec![
+        form_component(secret),
+        encode_path_value(secret),
+        json.trim_matches('"').to_string(),
+        secret.to_string(),
+    ];
+    forms.retain(|form| !form.is_empty());
+    forms.dedup();
+    forms
+}
+
+fn form_encode(body: &str) -> Result<String> {
+    let Ok(Value::Object(fields)) = serde_json::from_str::<Value>(body) else {
+        return Err(CallixError::InvalidBody(String::from(
+            "form bodies must render to a JSON object",
+        )));
+    };
+
+    let mut pairs = Vec::new();
+    for (key, value) in &fields {
+        let values = match value {
+            Value::Array(items) => items.iter().collect(),
+            value => vec![value],
+        };
+        for value in values {
+            let value = match value {
+                Value::Null => continue,
+                Value::String(s) => Cow::Borrowed(s.as_str()),
+                other => Cow::Owned(other.to_string()),
+            };
+            pairs.push(format!(
+                "{}={}",
+                form_component(key),
+                form_component(&value)
+            ));
+        }
+    }
+    Ok(pairs.join("&"))
+}
+
+fn form_component(value: &str) -> String {
+    utf8_percent_encode(value, QUERY_VALUE)
+        .to_string()
+        .replace("%20", "+")
+}
+
+fn json_type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+fn past_deadline(deadline: Option<Instant>, delay: Duration) -> bool {
+    deadline.is_some_and(|deadline| Instant::now() + delay >= deadline)
+}
+
+fn collect_strings<'v>(value: &'v Value, strings: &mut Vec<&'v str>) {
+    match value {
+        Value::String(s) => strings.push(s),
+        Value::Array(items) => items.iter().for_each(|item| collect_strings(item, strings)),
+        Value::Object(map) => map.values().for_each(|item| collect_strings(item, strings)),
+        _ => {}
+    }
+}
+
+fn merge_json(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_json(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+fn parse_url(url: &str) -> Result<Url> {
+    Url::parse(url).map_err(|_| CallixError::InvalidUrl(url.to_string()))
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+fn last_error(mut errors: Vec<CallixError>) -> CallixError {
+    errors.pop().unwrap_or(CallixError::MaxRetriesExceeded)
+}
+
+fn is_absolute_url(path: &str) -> bool {
+    ["http://", "https://"].iter().any(|scheme| {
+        path.get(..scheme.len())
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case(scheme))
+    })
+}
+
+fn join_url(base: &Url, path: &str) -> Result<Url> {
+    if is_absolute_url(path) {
+        return Url::parse(path).map_err(|_| CallixError::InvalidUrl(path.to_string()));
+    }
+
+    if path.is_empty() {
+        return Ok(base.clone());
+    }
+
+    let prefix = base.path().trim_end_matches('/');
+    let rest = path.trim_start_matches('/');
+
+    base.join(&format!("{}/{}", prefix, rest))
+        .map_err(|_| CallixError::InvalidUrl(path.to_string()))
+}
+
+fn is_signature_expired(url: &Url) -> bool {
+    let mut expires = None;
+    let mut signed_at = None;
+    let mut valid_for = None;
+
+    for (key, value) in url.query_pairs() {
+        match key.as_ref() {
+            "Expires" => expires = value.parse::<u64>().ok(),
+            "X-Amz-Date" | "X-Goog-Date" => signed_at = parse_signing_date(&value),
+            "X-Amz-Expires" | "X-Goog-Expires" => valid_for = value.parse::<u64>().ok(),
+            _ => {}
+        }
+    }
+
+    let deadline = match (expires, signed_at, valid_for) {
+        (Some(expires), _, _) => expires,
+        (None, Some(signed_at), Some(valid_for)) => signed_at.saturating_add(valid_for),
+        _ => return false,
+    };
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+
+    deadline < now
+}
+
+fn parse_signing_date(value: &str) -> Option<u64> {
+    let bytes = value.as_bytes();
+    if bytes.len() != 16 || bytes[8] != b'T' || bytes[15] != b'Z' {
+        return None;
+    }
+
+    let field = |range: std::ops::Range<usize>| value.get(range)?.parse::<u64>().ok();
+    let (year, month, day) = (field(0..4)?, field(4..6)?, field(6..8)?);
+    let (hour, minute, second) = (field(9..11)?, field(11..13)?, field(13..15)?);
+
+    unix_seconds(year, month, day, hour * 3_600 + minute * 60 + second)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CallixBuilder;
+    use crate::test_support::{
+        MockServer, Recorded, RecordingSleeper, Reply, endpoint, mock_client, provider,
+    };
+    use futures_util::StreamExt;
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    fn url(value: &str) -> Url {
+        Url::parse(value).unwrap()
+    }
+
+    #[test]
+    fn join_url_keeps_the_base_path_prefix() {
+        let base = url("https://api.example.com/v1/");
+        assert_eq!(
+            join_url(&base, "/chat/completions").unwrap().as_str(),
+            "https://api.example.com/v1/chat/completions"
+        );
+
+        let base = url("https://api.example.com/v1");
+        assert_eq!(
+            join_url(&base, "models").unwrap().as_str(),
+            "https://api.example.com/v1/models"
+        );
+    }
+
+    #[test]
+    fn join_url_with_empty_path_returns_the_base() {
+        let base = url("https://api.example.com/v1");
+        assert_eq!(join_url(&base, "").unwrap(), base);
+    }
+
+    #[test]
+    fn join_url_accepts_absolute_http_urls() {
+        let base = url("https://api.example.com/v1");
+        assert_eq!(
+            join_url(&base, "https://upload.example.com/files")
+                .unwrap()
+                .as_str(),
+            "https://upload.example.com/files"
+        );
+        assert_eq!(
+            join_url(&base, "HTTP://other.example.com/")
+                .unwrap()
+                .as_str(),
+            "http://other.example.com/"
+        );
+    }
+
+    #[test]
+    fn join_url_treats_colon_paths_as_relative() {
+        let base = url("https://generativelanguage.googleapis.com/v1beta");
+        assert_eq!(
+            join_url(&base, "models/gemini:generateContent")
+                .unwrap()
+                .as_str(),
+            "https://generativelanguage.googleapis.com/v1beta/models/gemini:generateContent"
+        );
+        assert_eq!(
+            join_url(&base, "urn:example").unwrap().as_str(),
+            "https://generativelanguage.googleapis.com/v1beta/urn:example"
+        );
+    }
+
+    #[test]
+    fn detects_an_elapsed_expires_param() {
+        assert!(is_signature_expired(&url("https://s3.test/o?Expires=1")));
+        assert!(!is_signature_expired(&url(
+            "https://s3.test/o?Expires=99999999999"
+        )));
+        assert!(!is_signature_expired(&url(
+            "https://s3.test/o?Expires=soon"
+        )));
+        assert!(!is_signature_expired(&url("https://s3.test/o")));
+    }
+
+    #[test]
+    fn detects_an_elapsed_signing_window() {
+        assert!(is_signature_expired(&url(
+            "https://s3.test/o?X-Amz-Date=20200101T000000Z&X-Amz-Expires=3600"
+        )));
+        assert!(!is_signature_expired(&url(
+            "https://gcs.test/o?X-Goog-Date=29990101T000000Z&X-Goog-Expires=3600"
+        )));
+        assert!(!is_signature_expired(&url(
+            "https://s3.test/o?X-Amz-Date=20200101T000000Z"
+        )));
+    }
+
+    #[test]
+    fn parses_signing_dates() {
+        assert_eq!(parse_signing_date("19700101T000100Z"), Some(60));
+        assert_eq!(parse_signing_date("20240229T120000Z"), Some(1_709_208_000));
+        assert_eq!(parse_signing_date("20241301T000000Z"), None);
+        assert_eq!(parse_signing_date("2024-01-01"), None);
+    }
+
+    #[test]
+    fn url_expiry_check_is_opt_in() {
+        let client = Client::new();
+        let provider = provider("https://s3.test");
+        let endpoint = endpoint("GET", "/object?Expires=1");
+
+        let request = RequestBuilder::new(&client, &provider, &endpoint, 0, Duration::ZERO);
+        assert!(request.build_url().is_ok());
+
+        let request = request.check_url_expiry(true);
+        assert!(matches!(request.build_url(), Err(CallixError::UrlExpired)));
+    }
+
+    async fn attempts_after_timeouts(
+        method: &str,
+        configure: impl FnOnce(RequestBuilder<'_>) -> RequestBuilder<'_>,
+    ) -> usize {
+        let server = MockServer::start(vec![Reply::ok("late").delay(Duration::from_secs(5))]).await;
+        let builder = CallixBuilder::new()
+            .timeout(Duration::from_millis(50))
+            .retries(2)
+            .retry_delay(Duration::ZERO);
+        let callix = mock_client(builder, &server, endpoint(method, "/"));
+
+        let result = configure(callix.request("mock", "call").unwrap())
+            .send()
+            .await;
+        assert!(result.is_err());
+        server.hits()
+    }
+
+    #[tokio::test]
+    async fn retries_idempotent_methods_on_transport_errors() {
+        assert_eq!(attempts_after_timeouts("GET", |request| request).await, 3);
+        assert_eq!(attempts_after_timeouts("PUT", |request| request).await, 3);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_post_on_transport_errors_by_default() {
+        assert_eq!(attempts_after_timeouts("POST", |request| request).await, 1);
+    }
+
+    #[tokio::test]
+    async fn retries_post_when_allowed_or_idempotency_keyed() {
+        let allowed = attempts_after_timeouts("POST", |request| request.retry_unsafe_methods(true));
+        assert_eq!(allowed.await, 3);
+
+        let keyed = attempts_after_timeouts("POST", |request| request.idempotency_key("k-1"));
+        assert_eq!(keyed.await, 3);
+    }
+
+    #[tokio::test]
+    async fn response_reports_the_elapsed_request_time() {
+        let delay = Duration::from_millis(30);
+        let server = MockServer::start(vec![Reply::ok("done").delay(delay)]).await;
+        let callix = mock_client(CallixBuilder::new(), &server, endpoint("GET", "/"));
+
+        let response = callix
+            .request("mock", "call")
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        assert!(
+            response.elapsed() >= delay,
+            "elapsed {:?}",
+            response.elapsed()
+        );
+    }
+
+    fn sent_body(request: &RequestBuilder<'_>) -> Value {
+        let body = request.render_body().unwrap().unwrap();
+        serde_json::from_str(&body).unwrap()
+    }
+
+    #[test]
+    fn merges_the_provider_default_body_under_the_endpoint_body() {
+        let client = Client::new();
+        let provider = ProviderConfig {
+            default_body: Some(json!({"api_version": "v1", "options": {"n": 1, "stream": false}})),
+            ..provider("https://api.test")
+        };
+        let endpoint = EndpointConfig {
+            body_template: Some(r#"{"model": "{{model}}", "options": {"n": 2}}"#.to_string()),
+            ..endpoint("POST", "/run")
+        };
+
+        let request = RequestBuilder::new(&client, &provider, &endpoint, 0, Duration::ZERO)
+            .var("model", "m-1");
+        assert_eq!(
+            sent_body(&request),
+            json!({"api_version": "v1", "model": "m-1", "options": {"n": 2, "stream": false}})
+        );
+    }
+
+    #[test]
+    fn endpoint_body_fields_override_provider_defaults() {
+        let client = Client::new();
+        let provider = ProviderConfig {
+            default_body: Some(json!({"api_version": "v1"})),
+            ..provider("https://api.test")
+        };
+        let endpoint = EndpointConfig {
+            body_template: Some(r#"{"api_version": "{{version}}"}"#.to_string()),
+            ..endpoint("POST", "/run")
+        };
+
+        let request = RequestBuilder::new(&client, &provider, &endpoint, 0, Duration::ZERO)
+            .var("version", "v2");
+        assert_eq!(sent_body(&request), json!({"api_version": "v2"}));
+    }
+
+    #[tokio::test]
+    async fn expect_status_accepts_listed_statuses() {
+        let server = MockServer::start(vec![Reply::new(201, &[], "created")]).await;
+        let callix = mock_client(CallixBuilder::new(), &server, endpoint("GET", "/"));
+
+        let request = callix.request("mock", "call").unwrap();
+        let response = request.expect_status(&[200, 201]).await.unwrap();
+        assert_eq!(response.status(), 201);
+    }
+
+    #[tokio::test]
+    async fn expect_status_reports_other_statuses_with_the_body() {
+        let server = MockServer::start(vec![Reply::new(400, &[], "bad input")]).await;
+        let callix = mock_client(CallixBuilder::new(), &server, endpoint("GET", "/"));
+
+        let request = callix.request("mock", "call").unwrap();
+        match request.expect_status(&[200, 201]).await {
+            Err(CallixError::ApiError { status, body }) => {
+                assert_eq!(status, 400);
+                assert_eq!(body, "bad input");
+            }
+            other => panic!("expected an API error, got {:?}", other.err()),
+        }
+    }
+
+    fn built_url(provider: &ProviderConfig, endpoint: &EndpointConfig, variables: Value) -> String {
+        let client = Client::new();
+        let variables = serde_json::from_value(variables).unwrap();
+        let request =
+            RequestBuilder::new(&client, provider, endpoint, 0, Duration::ZERO).vars(variables);
+        request.build_url().unwrap().to_string()
+    }
+
+    #[test]
+    fn build_url_keeps_inline_query_and_appends_params() {
+        let provider = provider("https://api.test/v2/");
+        let endpoint = EndpointConfig {
+            query_params: HashMap::from([("q".to_string(), "{{term}}".to_string())]),
+            ..endpoint("GET", "/search?sort=asc#top")
+        };
+
+        assert_eq!(
+            built_url(&provider, &endpoint, json!({"term": "a&b"})),
+            "https://api.test/v2/search?sort=asc&q=a%26b#top"
+        );
+    }
+
+    #[test]
+    fn array_variables_expand_into_repeated_query_params() {
+        let provider = ProviderConfig::new("https://api.test");
+        let endpoint = EndpointConfig::new("GET", "/items").query_param("id", "{{ids}}");
+
+        assert_eq!(
+            built_url(&provider, &endpoint, json!({"ids": [1, 2, 3]})),
+            "https://api.test/items?id=1&id=2&id=3"
+        );
+        assert_eq!(
+            built_url(&provider, &endpoint, json!({"ids": ["a b", "c&d"]})),
+            "https://api.test/items?id=a+b&id=c%26d"
+        );
+        assert_eq!(
+            built_url(&provider, &endpoint, json!({"ids": 7})),
+            "https://api.test/items?id=7"
+        );
+    }
+
+    #[test]
+    fn array_variables_inside_larger_templates_stay_a_single_param() {
+        let provider = ProviderConfig::new("https://api.test");
+        let endpoint = EndpointConfig::new("GET", "/items").query_param("filter", "id:{{ids}}");
+
+        let url = url(&built_url(&provider, &endpoint, json!({"ids": [1, 2]})));
+        let pairs: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(pairs[0].0, "filter");
+    }
+
+    #[test]
+    fn build_url_encodes_path_variables() {
+        let provider = ProviderConfig::new("https://{{host}}.test");
+        let endpoint = EndpointConfig::new("GET", "/files/{{name}}");
+
+        assert_eq!(
+            built_url(&provider, &endpoint, json!({"host": "eu", "name": "a b?c"})),
+            "https://eu.test/files/a%20b%3Fc"
+        );
+    }
+
+    #[test]
+    fn build_url_keeps_path_variables_in_one_segment() {
+        let provider = ProviderConfig::new("https://api.test");
+        let endpoint = EndpointConfig::new("GET", "/files/{{name}}/meta");
+
+        let url = |name: &str| built_url(&provider, &endpoint, json!({ "name": name }));
+        assert_eq!(url("../x"), "https://api.test/files/%2E%2E%2Fx/meta");
+        assert_eq!(url("a/b"), "https://api.test/files/a%2Fb/meta");
+        assert_eq!(url("v1.2"), "https://api.test/files/v1.2/meta");
+
+        let client = Client::new();
+        for name in [".", ".."] {
+            let request = RequestBuilder::new(&client, &provider, &endpoint, 0, Duration::ZERO)
+                .var("name", name);
+            assert!(matches!(
+                request.build_url(),
+                Err(CallixError::InvalidVariable { name, .. }) if name == "name"
+            ));
+        }
+    }
+
+    #[test]
+    fn provider_query_params_apply_beneath_endpoint_params() {
+        let mut provider = provider("https://api.test");
+        provider
+            .default_query_params
+            .insert("api-version".to_string(), "{{version}}".to_string());
+        provider
+            .default_query_params
+            .insert("format".to_string(), "json".to_string());
+
+        let query = |endpoint: &EndpointConfig, variables: Value| {
+            let url = url(&built_url(&provider, endpoint, variables));
+            let mut pairs: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+            pairs.sort();
+            pairs
+        };
+        let pair = |key: &str, value: &str| (key.to_string(), value.to_string());
+
+        let endpoint = endpoint("GET", "/items");
+        assert_eq!(
+            query(&endpoint, json!({"version": "2024 01"})),
+            [pair("api-version", "2024 01"), pair("format", "json")]
+        );
+
+        let endpoint = EndpointConfig {
+            query_params: HashMap::from([("format".to_string(), "xml".to_string())]),
+            ..endpoint
+        };
+        assert_eq!(
+            query(&endpoint, json!({"version": "1"})),
+            [pair("api-version", "1"), pair("format", "xml")]
+        );
+    }
+
+    #[test]
+    fn default_config_builds_azure_openai_urls() {
+        let config = crate::config::Config::default_config();
+        let provider = config.get_provider("azure_openai").unwrap();
+        let endpoint = provider.get_endpoint("chat").unwrap();
+
+        let variables = json!({
+            "resource": "contoso",
+            "deployment": "gpt-4o",
+            "api_version": "2024-06-01"
+        });
+        assert_eq!(
+            built_url(provider, endpoint, variables),
+            "https://contoso.openai.azure.com/openai/deployments/gpt-4o/chat/completions?api-version=2024-06-01"
+        );
+    }
+
+    #[tokio::test]
+    async fn auto_request_id_is_sent_and_exposed() {
+        let server = MockServer::start(vec![Reply::ok("ok")]).await;
+        let builder = CallixBuilder::new().auto_request_id("X-Request-ID");
+        let callix = mock_client(builder, &server, endpoint("GET", "/"));
+
+        let first = callix
+            .request("mock", "call")
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        let second = callix
+            .request("mock", "call")
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+
+        let sent = server.requests();
+        let first_id = first.request_id().unwrap();
+        assert!(Uuid::parse_str(first_id).is_ok());
+        assert_eq!(sent[0].header("x-request-id"), Some(first_id));
+        assert_eq!(sent[1].header("x-request-id"), second.request_id());
+        assert_ne!(first.request_id(), second.request_id());
+    }
+
+    #[tokio::test]
+    async fn auto_request_id_keeps_an_explicit_id() {
+        let server = MockServer::start(vec![Reply::ok("ok")]).await;
+        let builder = CallixBuilder::new().auto_request_id("X-Request-ID");
+        let callix = mock_client(builder, &server, endpoint("GET", "/"));
+
+        let request = callix.request("mock", "call").unwrap();
+        let response = request.header("x-request-id", "mine").send().await.unwrap();
+        assert_eq!(response.request_id(), Some("mine"));
+        assert_eq!(server.requests()[0].header("x-request-id"), Some("mine"));
+    }
+
+    async fn sent_headers(provider: &ProviderConfig, endpoint: &EndpointConfig) -> Recorded {
+        let server = MockServer::start(vec![Reply::ok("ok")]).await;
+        let provider = ProviderConfig {
+            base_url: server.url.clone(),
+            headers: provider.headers.clone(),
+            ..crate::test_support::provider(&server.url)
+        };
+
+        let client = Client::new();
+        RequestBuilder::new(&client, &provider, endpoint, 0, Duration::ZERO)
+            .var("token", "t-1")
+            .var("user", "ann")
+            .var("pass", "pw")
+            .send()
+            .await
+            .unwrap();
+        server.requests().remove(0)
+    }
+
+    #[tokio::test]
+    async fn endpoint_auth_overrides_provider_auth() {
+        let mut provider = provider("https://api.test");
+        provider
+            .headers
+            .insert("Authorization".to_string(), "Bearer {{token}}".to_string());
+
+        let private = endpoint("GET", "/private");
+        let sent = sent_headers(&provider, &private).await;
+        assert_eq!(sent.header("authorization"), Some("Bearer t-1"));
+
+        let mut public = endpoint("GET", "/public");
+        public.auth = Some(AuthConfig::None);
+        let sent = sent_headers(&provider, &public).await;
+        assert_eq!(sent.header("authorization"), None);
+
+        let mut basic = endpoint("GET", "/basic");
+        basic.auth = Some(AuthConfig::Basic {
+            username: "{{user}}".to_string(),
+            password: Some("{{pass}}".to_string()),
+        });
+        let expected = format!("Basic {}", BASE64_STANDARD.encode("ann:pw"));
+        let sent = sent_headers(&provider, &basic).await;
+        assert_eq!(sent.header("authorization"), Some(expected.as_str()));
+
+        let mut keyed = endpoint("GET", "/keyed");
+        keyed.auth = Some(AuthConfig::Header {
+            name: "X-Api-Key".to_string(),
+            value: "{{token}}".to_string(),
+        });
+        let sent = sent_headers(&provider, &keyed).await;
+        assert_eq!(sent.header("x-api-key"), Some("t-1"));
+        assert_eq!(sent.header("authorization"), None);
+    }
+
+    #[tokio::test]
+    async fn namespaced_variables_resolve_independently() {
+        let server = MockServer::start(vec![Reply::ok("ok")]).await;
+        let mut provider = provider(&server.url);
+        provider
+            .headers
+            .insert("X-Key".to_string(), "{{env.key}}".to_string());
+        let endpoint = EndpointConfig {
+            body: Some(json!({"key": "{{body.key}}"})),
+            ..endpoint("POST", "/run")
+        };
+
+        let client = Client::new();
+        let request = RequestBuilder::new(&client, &provider, &endpoint, 0, Duration::ZERO)
+            .var_ns("env", "key", "from-env")
+            .var_ns("body", "key", "from-body")
+            .var("key", "flat");
+        assert_eq!(sent_body(&request), json!({"key": "from-body"}));
+
+        request.send().await.unwrap();
+        assert_eq!(server.requests()[0].header("x-key"), Some("from-env"));
+    }
+
+    #[test]
+    fn explain_reports_the_trace_with_secrets_redacted() {
+        let client = Client::new();
+        let provider = ProviderConfig::new("https://api.test");
+        let endpoint = EndpointConfig::new("POST", "/users/{{user}}")
+            .body_template(r#"{"token": "{{token}}"}"#);
+
+        let request = RequestBuilder::new(&client, &provider, &endpoint, 0, Duration::ZERO)
+            .var("user", "ann")
+            .var_secret("token", "s3cret");
+
+        let explanation = request.explain().unwrap();
+        assert_eq!(explanation.url, "https://api.test/users/ann");
+        assert_eq!(
+            explanation.body.as_deref(),
+            Some(r#"{"token": "[REDACTED]"}"#)
+        );
+        assert!(
+            explanation
+                .trace
+                .contains(&("user".to_string(), "ann".to_string()))
+        );
+        assert!(
+            explanation
+                .trace
+                .contains(&("token".to_string(), REDACTED.to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn max_attempts_caps_retries_within_the_deadline() {
+        let server = MockServer::start(vec![Reply::new(503, &[], "busy")]).await;
+        let builder = CallixBuilder::new()
+            .retries(10)
+            .retry_delay(Duration::ZERO)
+            .total_deadline(Duration::from_secs(30))
+            .retry_on_status(&[503])
+            .max_attempts(2);
+        let callix = mock_client(builder, &server, EndpointConfig::new("GET", "/"));
+
+        let response = callix.request("mock", "call").unwrap().send().await;
+        assert_eq!(response.unwrap().status(), 503);
+        assert_eq!(server.hits(), 2);
+    }
+
+    #[tokio::test]
+    async fn total_deadline_aborts_before_a_backoff_that_would_overrun_it() {
+        let server = MockServer::start(vec![Reply::new(503, &[], "busy")]).await;
+        let builder = CallixBuilder::new()
+            .retries(3)
+            .retry_delay(Duration::from_secs(10))
+            .total_deadline(Duration::from_millis(300))
+            .retry_on_status(&[503]);
+        let callix = mock_client(builder, &server, EndpointConfig::new("GET", "/"));
+
+        let started = Instant::now();
+        let result = callix.request("mock", "call").unwrap().send().await;
+        assert!(matches!(result, Err(CallixError::TimeoutError)));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(server.hits(), 1);
+    }
+
+    fn body_retry_client(server: &MockServer, retries: u32) -> crate::Callix {
+        let builder = CallixBuilder::new()
+            .retries(retries)
+            .retry_delay(Duration::ZERO);
+        let endpoint =
+            EndpointConfig::new("POST", "/jobs").retry_on_body("/code", vec![json!(503)]);
+        mock_client(builder, server, endpoint)
+    }
+
+    #[tokio::test]
+    async fn retry_on_body_retries_until_a_success_code() {
+        let busy = Reply::json(200, r#"{"code":503}"#);
+        let done = Reply::json(200, r#"{"code":0,"data":"ok"}"#);
+        let server = MockServer::start(vec![busy.clone(), busy, done]).await;
+        let callix = body_retry_client(&server, 3);
+
+        let response = callix
+            .request("mock", "call")
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body, json!({"code": 0, "data": "ok"}));
+        assert_eq!(server.hits(), 3);
+    }
+
+    #[tokio::test]
+    async fn retry_on_body_returns_the_last_response_when_retries_run_out() {
+        let server = MockServer::start(vec![Reply::json(200, r#"{"code":503}"#)]).await;
+        let callix = body_retry_client(&server, 1);
+
+        let response = callix
+            .request("mock", "call")
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), r#"{"code":503}"#);
+        assert_eq!(server.hits(), 2);
+    }
+
+    async fn retry_status_hits(endpoint: EndpointConfig, status: u16) -> usize {
+        let server = MockServer::start(vec![Reply::new(status, &[], "no")]).await;
+        let builder = CallixBuilder::new()
+            .retries(2)
+            .retry_delay(Duration::ZERO)
+            .retry_on_status(&[503]);
+        let callix = mock_client(builder, &server, endpoint);
+
+        let response = callix.request("mock", "call").unwrap().send().await;
+        assert_eq!(response.unwrap().status(), status);
+        server.hits()
+    }
+
+    #[tokio::test]
+    async fn endpoint_retry_on_status_overrides_the_global_set() {
+        let read = EndpointConfig::new("GET", "/read").retry_on_status(&[409]);
+        let write = EndpointConfig::new("PUT", "/write");
+
+        assert_eq!(retry_status_hits(read.clone(), 409).await, 3);
+        assert_eq!(retry_status_hits(write.clone(), 409).await, 1);
+        assert_eq!(retry_status_hits(read, 503).await, 1);
+        assert_eq!(retry_status_hits(write, 503).await, 3);
+    }
+
+    #[tokio::test]
+    async fn endpoint_retry_on_status_opts_post_into_retries() {
+        let listed = EndpointConfig::new("POST", "/jobs").retry_on_status(&[409]);
+        let unlisted = EndpointConfig::new("POST", "/jobs");
+
+        assert_eq!(retry_status_hits(listed, 409).await, 3);
+        assert_eq!(retry_status_hits(unlisted, 503).await, 1);
+    }
+
+    #[tokio::test]
+    async fn total_deadline_bounds_a_slow_attempt() {
+        let server = MockServer::start(vec![Reply::ok("late").delay(Duration::from_secs(5))]).await;
+        let builder = CallixBuilder::new()
+            .timeout(Duration::from_secs(30))
+            .total_deadline(Duration::from_millis(100));
+        let callix = mock_client(builder, &server, EndpointConfig::new("GET", "/"));
+
+        let started = Instant::now();
+        let result = callix.request("mock", "call").unwrap().send().await;
+        assert!(matches!(result, Err(CallixError::TimeoutError)));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn endpoint_timeouts_override_the_client_timeout() {
+        let slow = Reply::ok("done").delay(Duration::from_millis(300));
+        let server = MockServer::start(vec![slow]).await;
+        let provider = ProviderConfig::new(&server.url)
+            .endpoint(
+                "quick",
+                EndpointConfig::new("GET", "/quick").timeout(Duration::from_millis(50)),
+            )
+            .endpoint(
+                "export",
+                EndpointConfig::new("GET", "/export").timeout(Duration::from_secs(10)),
+            );
+        let mut callix = CallixBuilder::new()
+            .timeout(Duration::from_millis(100))
+            .retries(0)
+            .build()
+            .unwrap();
+        callix.register_provider("api", provider).unwrap();
+
+        let quick = callix.request("api", "quick").unwrap().send().await;
+        assert!(matches!(quick, Err(CallixError::TimeoutError)));
+
+        let export = callix.request("api", "export").unwrap().send().await;
+        assert_eq!(export.unwrap().text().await.unwrap(), "done");
+    }
+
+    #[test]
+    fn request_timeout_prefers_endpoint_then_provider() {
+        let client = Client::new();
+        let provider = ProviderConfig {
+            timeout: Some(20),
+            ..ProviderConfig::new("https://api.test")
+        };
+        let inherited = EndpointConfig::new("GET", "/");
+        let overridden = EndpointConfig::new("GET", "/").timeout(Duration::from_millis(250));
+
+        let timeout = |endpoint| builder(&client, &provider, endpoint).request_timeout();
+        assert_eq!(timeout(&inherited), Some(Duration::from_secs(20)));
+        assert_eq!(timeout(&overridden), Some(Duration::from_millis(250)));
+    }
+
+    async fn hedged_body(method: &str, replies: Vec<Reply>, delay: Duration) -> (String, usize) {
+        let server = MockServer::start(replies).await;
+        let builder = CallixBuilder::new().hedge(delay);
+        let callix = mock_client(builder, &server, EndpointConfig::new(method, "/"));
+
+        let response = callix
+            .request("mock", "call")
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        (response.text().await.unwrap(), server.hits())
+    }
+
+    #[tokio::test]
+    async fn hedging_takes_the_faster_second_request() {
+        let slow = Reply::ok("slow").delay(Duration::from_secs(5));
+        let started = Instant::now();
+
+        let (body, hits) = hedged_body(
+            "GET",
+            vec![slow, Reply::ok("fast")],
+            Duration::from_millis(50),
+        )
+        .await;
+        assert_eq!(body, "fast");
+        assert_eq!(hits, 2);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn hedging_waits_for_the_delay_before_firing() {
+        let (body, hits) = hedged_body(
+            "GET",
+            vec![Reply::ok("first"), Reply::ok("second")],
+            Duration::from_secs(5),
+        )
+        .await;
+        assert_eq!(body, "first");
+        assert_eq!(hits, 1);
+    }
+
+    #[tokio::test]
+    async fn hedging_skips_non_idempotent_methods() {
+        let slow = Reply::ok("slow").delay(Duration::from_millis(200));
+
+        let (body, hits) = hedged_body(
+            "POST",
+            vec![slow, Reply::ok("fast")],
+            Duration::from_millis(20),
+        )
+        .await;
+        assert_eq!(body, "slow");
+        assert_eq!(hits, 1);
+    }
+
+    #[tokio::test]
+    async fn inspect_observes_the_prepared_request_before_sending() {
+        let server = MockServer::start(vec![Reply::ok("ok")]).await;
+        let endpoint = EndpointConfig::new("GET", "/items/{{id}}");
+        let callix = mock_client(CallixBuilder::new(), &server, endpoint);
+
+        let seen = Mutex::new(Vec::new());
+        let response = callix
+            .request("mock", "call")
+            .unwrap()
+            .var("id", 7)
+            .inspect(|prepared| {
+                let mut seen = seen.lock().unwrap();
+                seen.push((prepared.method.clone(), prepared.url.path().to_string()));
+            })
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            *seen.lock().unwrap(),
+            [(Method::GET, "/items/7".to_string())]
+        );
+        assert_eq!(server.requests()[0].target, "/items/7");
+    }
+
+    #[tokio::test]
+    async fn max_response_size_applies_to_sent_requests() {
+        let reply = Reply::chunked(200, &[], &[b"0123", b"4567", b"89"]);
+        let server = MockServer::start(vec![reply]).await;
+        let builder = CallixBuilder::new().max_response_size(8);
+        let callix = mock_client(builder, &server, EndpointConfig::new("GET", "/"));
+
+        let response = callix
+            .request("mock", "call")
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        assert!(matches!(
+            response.bytes().await,
+            Err(CallixError::ResponseTooLarge(8))
+        ));
+    }
+
+    fn builder<'a>(
+        client: &'a Client,
+        provider: &'a ProviderConfig,
+        endpoint: &'a EndpointConfig,
+    ) -> RequestBuilder<'a> {
+        RequestBuilder::new(client, provider, endpoint, 0, Duration::ZERO)
+    }
+
+    #[test]
+    fn coerced_variables_render_as_json_scalars() {
+        let client = Client::new();
+        let provider = ProviderConfig::new("https://api.test");
+        let endpoint = EndpointConfig {
+            body: Some(json!({
+                "temperature": "{{temperature}}",
+                "n": "{{n}}",
+                "stream": "{{stream}}"
+            })),
+            ..EndpointConfig::new("POST", "/run")
+        };
+
+        let request = builder(&client, &provider, &endpoint)
+            .var_number("temperature", " 0.7 ")
+            .var_number("n", "3")
+            .var_bool("stream", "TRUE");
+        assert_eq!(
+            sent_body(&request),
+            json!({"temperature": 0.7, "n": 3, "stream": true})
+        );
+
+        let request = builder(&client, &provider, &endpoint)
+            .var("temperature", "0.7")
+            .var("n", "3")
+            .var("stream", "true");
+        assert_eq!(
+            sent_body(&request),
+            json!({"temperature": "0.7", "n": "3", "stream": "true"})
+        );
+    }
+
+    #[test]
+    fn unparseable_coercions_fail_with_the_variable_name() {
+        let client = Client::new();
+        let provider = ProviderConfig::new("https://api.test");
+        let endpoint = EndpointConfig::new("GET", "/");
+
+        let request = builder(&client, &provider, &endpoint)
+            .var_number("temperature", "warm")
+            .var_bool("stream", "maybe");
+        match request.build_request() {
+            Err(CallixError::InvalidVariable { name, reason }) => {
+                assert_eq!(name, "temperature");
+                assert_eq!(reason, "expected a number, got 'warm'");
+            }
+            other => panic!("expected an invalid variable, got {:?}", other.err()),
+        }
+
+        let request = builder(&client, &provider, &endpoint).var_bool("stream", "yes");
+        assert!(matches!(
+            request.build_request(),
+            Err(CallixError::InvalidVariable { name, .. }) if name == "stream"
+        ));
+    }
+
+    #[test]
+    fn merge_vars_json_adds_each_key_as_a_variable() {
+        let client = Client::new();
+        let provider = ProviderConfig::new("https://api.test");
+        let endpoint =
+            EndpointConfig::new("POST", "/{{b}}").body(json!({"a": "{{a}}", "b": "{{b}}"}));
+
+        let request = builder(&client, &provider, &endpoint)
+            .var("b", "old")
+            .merge_vars_json(r#"{"a":1,"b":"x"}"#);
+        let prepared = request.build_request().unwrap();
+        assert_eq!(prepared.url.as_str(), "https://api.test/x");
+        assert_eq!(
+            serde_json::from_str::<Value>(&prepared.body.unwrap()).unwrap(),
+            json!({"a": 1, "b": "x"})
+        );
+    }
+
+    #[test]
+    fn var_raw_splices_json_fragments_into_body_templates() {
+        let client = Client::new();
+        let provider = ProviderConfig::new("https://api.test");
+        let endpoint = EndpointConfig::new("POST", "/search")
+            .body_template(r#"{"filters": {{filters}}, {{extra}}}"#);
+
+        let request = builder(&client, &provider, &endpoint)
+            .var_raw("filters", r#"{"status": "open"}"#)
+            .var_raw("extra", r#""tags": ["a", "b"]"#);
+        assert_eq!(
+            sent_body(&request),
+            json!({"filters": {"status": "open"}, "tags": ["a", "b"]})
+        );
+    }
+
+    #[test]
+    fn var_raw_replaces_whole_placeholders_in_structured_bodies() {
+        let client = Client::new();
+        let provider = ProviderConfig::new("https://api.test");
+        let endpoint = EndpointConfig::new("POST", "/search")
+            .body(json!({"filters": "{{filters}}", "label": "{{label}}"}));
+        let fragment = r#"{"status": "open"}"#;
+
+        let request = builder(&client, &provider, &endpoint)
+            .var_raw("filters", fragment)
+            .var("label", fragment);
+        assert_eq!(
+            sent_body(&request),
+            json!({"filters": {"status": "open"}, "label": fragment})
+        );
+
+        let request = builder(&client, &provider, &endpoint)
+            .var_raw("filters", "{not json")
+            .var("label", "x");
+        assert!(matches!(
+            request.build_request(),
+            Err(CallixError::InvalidBody(message)) if message.starts_with("raw variable 'filters'")
+        ));
+    }
+
+    #[derive(Default)]
+    struct FakeVault {
+        lookups: Mutex<Vec<String>>,
+    }
+
+    impl VariableSource for FakeVault {
+        fn get(&self, key: &str) -> Option<Value> {
+            self.lookups.lock().unwrap().push(key.to_string());
+            match key {
+                "api_key" => Some(json!("vault-key")),
+                "db" => Some(json!({"region": "eu"})),
+                _ => None,
+            }
+        }
+    }
+
+    #[test]
+    fn var_sources_supply_missing_variables_once() {
+        let client = Client::new();
+        let provider =
+            ProviderConfig::new("https://api.test").header("Authorization", "Bearer {{api_key}}");
+        let endpoint = EndpointConfig::new("POST", "/{{db.region}}/{{user}}")
+            .body(json!({"key": "{{api_key}}", "user": "{{user}}"}));
+        let vault = Arc::new(FakeVault::default());
+
+        let request = builder(&client, &provider, &endpoint)
+            .var("user", "ann")
+            .var_source(Arc::clone(&vault) as Arc<dyn VariableSource>);
+        let prepared = request.build_request().unwrap();
+        assert_eq!(prepared.url.as_str(), "https://api.test/eu/ann");
+        assert_eq!(prepared.headers["authorization"], "Bearer vault-key");
+        assert_eq!(
+            serde_json::from_str::<Value>(&prepared.body.unwrap()).unwrap(),
+            json!({"key": "vault-key", "user": "ann"})
+        );
+
+        let mut lookups = vault.lookups.lock().unwrap().clone();
+        lookups.sort();
+        assert_eq!(lookups, ["api_key", "db", "db.region"]);
+    }
+
+    #[test]
+    fn earlier_var_sources_take_precedence() {
+        let client = Client::new();
+        let provider = ProviderConfig::new("https://api.test");
+        let endpoint = EndpointConfig::new("GET", "/{{api_key}}/{{team}}");
+        let overrides: HashMap<String, Value> = HashMap::from([
+            ("api_key".to_string(), json!("override")),
+            ("team".to_string(), json!("core")),
+        ]);
+
+        let request = builder(&client, &provider, &endpoint)
+            .var_source(Arc::new(overrides))
+            .var_source(Arc::new(FakeVault::default()));
+        assert_eq!(
+            request.build_request().unwrap().url.as_str(),
+            "https://api.test/override/core"
+        );
+    }
+
+    #[test]
+    fn setting_variables_after_a_render_refreshes_sourced_values() {
+        let client = Client::new();
+        let provider = ProviderConfig::new("https://api.test");
+        let endpoint = EndpointConfig::new("GET", "/{{api_key}}/{{team}}");
+        let vault: Arc<dyn VariableSource> = Arc::new(FakeVault::default());
+
+        let request = builder(&client, &provider, &endpoint)
+            .var_source(vault)
+            .var("team", "core");
+        assert_eq!(
+            request.explain().unwrap().url,
+            "https://api.test/vault-key/core"
+        );
+
+        let request = request.var("api_key", "local");
+        assert_eq!(
+            request.explain().unwrap().url,
+            "https://api.test/local/core"
+        );
+
+        let overrides: HashMap<String, Value> = HashMap::from([("team".to_string(), json!("ops"))]);
+        let request = builder(&client, &provider, &endpoint).var("api_key", "k");
+        assert!(request.explain().is_err());
+        let request = request.var_source(Arc::new(overrides));
+        assert_eq!(request.explain().unwrap().url, "https://api.test/k/ops");
+    }
+
+    #[test]
+    fn when_applies_the_closure_only_if_the_condition_holds() {
+        let client = Client::new();
+        let provider = ProviderConfig::new("https://api.test");
+        let endpoint = EndpointConfig::new("GET", "/");
+
+        let headers = |debug: bool| {
+            let mut calls = 0;
+            let request = builder(&client, &provider, &endpoint).when(debug, |request| {
+                calls += 1;
+                request.header("X-Debug", "1")
+            });
+            let headers = request.build_request().unwrap().headers;
+            (headers.get("x-debug").cloned(), calls)
+        };
+        assert_eq!(headers(true), (Some("1".to_string()), 1));
+        assert_eq!(headers(false), (None, 0));
+    }
+
+    #[test]
+    fn merge_vars_json_rejects_non_objects_and_malformed_json() {
+        let client = Client::new();
+        let provider = ProviderConfig::new("https://api.test");
+        let endpoint = EndpointConfig::new("GET", "/");
+
+        let request = builder(&client, &provider, &endpoint).merge_vars_json("[1, 2]");
+        match request.build_request() {
+            Err(CallixError::InvalidVariable { name, reason }) => {
+                assert_eq!(name, MERGED_VARIABLES);
+                assert_eq!(reason, "expected a JSON object, got an array");
+            }
+            other => panic!("expected an invalid variable, got {:?}", other.err()),
+        }
+
+        let request = builder(&client, &provider, &endpoint).merge_vars_json("{oops");
+        assert!(matches!(
+            request.build_request(),
+            Err(CallixError::InvalidVariable { name, .. }) if name == MERGED_VARIABLES
+        ));
+    }
+
+    #[tokio::test]
+    async fn endpoint_accept_sets_the_accept_header() {
+        let provider = ProviderConfig::new("https://api.test").header("Accept", "application/json");
+
+        let endpoint = EndpointConfig::new("GET", "/report").accept("application/xml");
+        let sent = sent_headers(&provider, &endpoint).await;
+        assert_eq!(sent.header("accept"), Some("application/xml"));
+
+        let endpoint = EndpointConfig::new("GET", "/report");
+        let sent = sent_headers(&provider, &endpoint).await;
+        assert_eq!(sent.header("accept"), Some("application/json"));
+    }
+
+    #[tokio::test]
+    async fn send_all_retries_errors_collects_every_failed_attempt() {
+        let server = MockServer::start(vec![Reply::ok("late").delay(Duration::from_secs(5))]).await;
+        let builder = CallixBuilder::new()
+            .timeout(Duration::from_millis(50))
+            .retries(2)
+            .retry_delay(Duration::ZERO);
+        let callix = mock_client(builder, &server, EndpointConfig::new("GET", "/"));
+
+        let request = callix.request("mock", "call").unwrap();
+        let errors = request.send_all_retries_errors().await.err().unwrap();
+        assert_eq!(errors.len(), 3);
+        assert!(
+            errors
+                .iter()
+                .all(|error| matches!(error, CallixError::TimeoutError))
+        );
+    }
+
+    #[tokio::test]
+    async fn send_all_retries_errors_returns_the_eventual_response() {
+        let server = MockServer::start(vec![Reply::ok("ok")]).await;
+        let callix = mock_client(
+            CallixBuilder::new(),
+            &server,
+            EndpointConfig::new("GET", "/"),
+        );
+
+        let request = callix.request("mock", "call").unwrap();
+        let response = request.send_all_retries_errors().await.unwrap();
+        assert_eq!(response.status(), 200);
+    }
+
+    #[test]
+    fn path_variables_are_percent_encoded_per_segment() {
+        let provider = ProviderConfig::new("https://api.test");
+        let endpoint = EndpointConfig::new("GET", "/files/{{dir}}/{{name}}");
+
+        assert_eq!(
+            built_url(
+                &provider,
+                &endpoint,
+                json!({"dir": "a/b", "name": "my file#1?.txt"})
+            ),
+            "https://api.test/files/a%2Fb/my%20file%231%3F.txt"
+        );
+    }
+
+    #[tokio::test]
+    async fn accept_encoding_is_sent_and_the_used_encoding_exposed() {
+        let reply = Reply::new(200, &[("content-encoding", "x-custom")], "raw");
+        let server = MockServer::start(vec![reply]).await;
+        let builder = CallixBuilder::new().accept_encoding("identity");
+        let callix = mock_client(builder, &server, EndpointConfig::new("GET", "/"));
+
+        let response = callix
+            .request("mock", "call")
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            server.requests()[0].header("accept-encoding"),
+            Some("identity")
+        );
+        assert_eq!(response.content_encoding(), Some("x-custom"));
+    }
+
+    #[cfg(feature = "multipart")]
+    #[tokio::test]
+    async fn multipart_sends_a_json_part_and_a_file_part() {
+        let server = MockServer::start(vec![Reply::ok("ok")]).await;
+        let endpoint =
+            EndpointConfig::new("POST", "/upload").body_template(r#"{"title": "{{title}}"}"#);
+        let callix = mock_client(CallixBuilder::new(), &server, endpoint);
+
+        callix
+            .request("mock", "call")
+            .unwrap()
+            .var("title", "cat")
+            .multipart_json_and_file(
+                "metadata",
+                "file",
+                "cat.png",
+                b"PNGDATA".to_vec(),
+                "image/png",
+            )
+            .send()
+            .await
+            .unwrap();
+
+        let request = &server.requests()[0];
+        assert!(
+            request
+                .header("content-type")
+                .unwrap()
+                .starts_with("multipart/form-data; boundary=")
+        );
+        let body = request.body_text();
+        let metadata = body.find("name=\"metadata\"").unwrap();
+        let file = body.find("name=\"file\"; filename=\"cat.png\"").unwrap();
+        assert!(body[metadata..file].contains("Content-Type: application/json"));
+        assert!(body[metadata..file].contains(r#"{"title": "cat"}"#));
+        assert!(body[file..].contains("Content-Type: image/png"));
+        assert!(body[file..].contains("PNGDATA"));
+    }
+
+    #[test]
+    fn templated_base_urls_resolve_per_request() {
+        let mut provider = ProviderConfig::new("https://{{tenant}}.api.test/v1");
+        provider.prepare();
+        assert!(provider.parsed_base_url.is_none());
+        let endpoint = EndpointConfig::new("GET", "/me");
+
+        assert_eq!(
+            built_url(&provider, &endpoint, json!({"tenant": "acme"})),
+            "https://acme.api.test/v1/me"
+        );
+        assert_eq!(
+            built_url(&provider, &endpoint, json!({"tenant": "globex"})),
+            "https://globex.api.test/v1/me"
+        );
+
+        let mut provider = ProviderConfig::new("https://api.test/v1");
+        provider.prepare();
+        assert!(provider.parsed_base_url.is_some());
+        assert_eq!(
+            built_url(&provider, &endpoint, json!({})),
+            "https://api.test/v1/me"
+        );
+    }
+
+    #[tokio::test]
+    async fn diagnostics_report_attempts_elapsed_and_final_url() {
+        let server = MockServer::start(vec![
+            Reply::new(503, &[], "busy"),
+            Reply::new(503, &[], "busy"),
+            Reply::ok("ok"),
+        ])
+        .await;
+        let builder = CallixBuilder::new()
+            .retries(3)
+            .retry_delay(Duration::ZERO)
+            .retry_on_status(&[503]);
+        let callix = mock_client(builder, &server, EndpointConfig::new("GET", "/status"));
+
+        let request = callix.request("mock", "call").unwrap();
+        let (response, diagnostics) = request.send_with_diagnostics().await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(diagnostics.attempts, 3);
+        assert!(diagnostics.elapsed > Duration::ZERO);
+        assert_eq!(
+            diagnostics.final_url.as_str(),
+            format!("{}/status", server.url)
+        );
+    }
+
+    #[tokio::test]
+    async fn paginate_follows_next_links_until_the_last_page() {
+        let server = MockServer::start(vec![
+            Reply::new(200, &[("link", r#"</items?page=2>; rel="next""#)], "1"),
+            Reply::new(200, &[("link", r#"</items?page=3>; rel="next""#)], "2"),
+            Reply::ok("3"),
+        ])
+        .await;
+        let callix = mock_client(
+            CallixBuilder::new(),
+            &server,
+            EndpointConfig::new("GET", "/items"),
+        );
+
+        let pages: Vec<_> = callix
+            .request("mock", "call")
+            .unwrap()
+            .paginate()
+            .collect()
+            .await;
+        let mut bodies = Vec::new();
+        for page in pages {
+            bodies.push(page.unwrap().text().await.unwrap());
+        }
+
+        assert_eq!(bodies, ["1", "2", "3"]);
+        let targets: Vec<_> = server.requests().into_iter().map(|r| r.target).collect();
+        assert_eq!(targets, ["/items", "/items?page=2", "/items?page=3"]);
+    }
+
+    #[tokio::test]
+    async fn paginate_stops_after_an_error() {
+        let server = MockServer::start(vec![Reply::ok("late").delay(Duration::from_secs(5))]).await;
+        let builder = CallixBuilder::new().timeout(Duration::from_millis(50));
+        let callix = mock_client(builder, &server, EndpointConfig::new("GET", "/items"));
+
+        let pages: Vec<_> = callix
+            .request("mock", "call")
+            .unwrap()
+            .paginate()
+            .collect()
+            .await;
+        assert!(matches!(pages[..], [Err(CallixError::TimeoutError)]));
+    }
+
+    #[tokio::test]
+    async fn success_statuses_redefine_error_for_status() {
+        let server = MockServer::start(vec![Reply::ok(r#"{"fault": "soap"}"#)]).await;
+        let builder = CallixBuilder::new().success_statuses(&[201, 204]);
+        let callix = mock_client(builder, &server, EndpointConfig::new("GET", "/"));
+
+        let response = callix
+            .request("mock", "call")
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        assert!(!response.is_success());
+        match response.error_for_status().await {
+            Err(CallixError::ApiError { status, body }) => {
+                assert_eq!(status, 200);
+                assert_eq!(body, r#"{"fault": "soap"}"#);
+            }
+            other => panic!("expected an API error, got {:?}", other.err()),
+        }
+    }
+
+    #[tokio::test]
+    async fn default_success_statuses_are_2xx() {
+        let server = MockServer::start(vec![Reply::new(204, &[], "")]).await;
+        let callix = mock_client(
+            CallixBuilder::new(),
+            &server,
+            EndpointConfig::new("GET", "/"),
+        );
+
+        let response = callix
+            .request("mock", "call")
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        assert!(response.error_for_status().await.is_ok());
+    }
+
+    fn assert_traceparent(value: &str, trace_id: Option<&str>) {
+        let parts: Vec<&str> = value.split('-').collect();
+        assert_eq!(parts.len(), 4, "traceparent {}", value);
+        assert_eq!(parts[0], "00");
+        assert_eq!(parts[1].len(), 32);
+        assert_eq!(parts[2].len(), 16);
+        assert_eq!(parts[3], "01");
+        for part in &parts[1..3] {
+            assert!(
+                part.bytes()
+                    .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+            );
+        }
+        if let Some(trace_id) = trace_id {
+            assert_eq!(parts[1], trace_id);
+        }
+    }
+
+    #[test]
+    fn with_trace_id_formats_a_w3c_traceparent() {
+        let client = Client::new();
+        let provider = ProviderConfig::new("https://api.test");
+        let endpoint = EndpointConfig::new("GET", "/");
+
+        let request = builder(&client, &provider, &endpoint)
+            .with_trace_id("4BF92F3577B34DA6A3CE929D0E0E4736");
+        let headers = request.build_request().unwrap().headers;
+        assert_traceparent(
+            &headers["traceparent"],
+            Some("4bf92f3577b34da6a3ce929d0e0e4736"),
+        );
+
+        let request = builder(&client, &provider, &endpoint).with_new_trace();
+        assert_traceparent(
+            &request.build_request().unwrap().headers["traceparent"],
+            None,
+        );
+    }
+
+    #[test]
+    fn with_trace_id_rejects_malformed_ids() {
+        let client = Client::new();
+        let provider = ProviderConfig::new("https://api.test");
+        let endpoint = EndpointConfig::new("GET", "/");
+
+        for trace_id in [
+            "abc",
+            "00000000000000000000000000000000",
+            "4bf92f3577b34da6a3ce929d0e0e473g",
+        ] {
+            let request = builder(&client, &provider, &endpoint).with_trace_id(trace_id);
+            match request.build_request() {
+                Err(CallixError::ValidationError(message)) => {
+                    assert!(message.contains(trace_id), "message {}", message);
+                }
+                other => panic!("expected a validation error, got {:?}", other.err()),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn yaml_body_templates_are_sent_as_json() {
+        let server = MockServer::start(vec![Reply::ok("ok")]).await;
+        let mut endpoint = EndpointConfig::new("POST", "/chat").body_template(
+            "model: {{model}}\nmessages:\n  - role: user\n    content: \"{{prompt}}\"\n",
+        );
+        endpoint.body_format = BodyFormat::Yaml;
+        let callix = mock_client(CallixBuilder::new(), &server, endpoint);
+
+        callix
+            .request("mock", "call")
+            .unwrap()
+            .var("model", "m-1")
+            .var("prompt", "hi")
+            .send()
+            .await
+            .unwrap();
+
+        let request = &server.requests()[0];
+        assert_eq!(request.header("content-type"), Some("application/json"));
+        let body: Value = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(
+            body,
+            json!({"model": "m-1", "messages": [{"role": "user", "content": "hi"}]})
+        );
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn toml_body_templates_render_to_json() {
+        let client = Client::new();
+        let provider = ProviderConfig::new("https://api.test");
+        let mut endpoint = EndpointConfig::new("POST", "/run")
+            .body_template("model = \"{{model}}\"\n\n[options]\nn = {{n}}\n");
+        endpoint.body_format = BodyFormat::Toml;
+
+        let request = builder(&client, &provider, &endpoint)
+            .var("model", "m-1")
+            .var("n", 2);
+        assert_eq!(
+            sent_body(&request),
+            json!({"model": "m-1", "options": {"n": 2}})
+        );
+    }
+
+    #[test]
+    fn invalid_yaml_bodies_are_reported() {
+        let client = Client::new();
+        let provider = ProviderConfig::new("https://api.test");
+        let mut endpoint = EndpointConfig::new("POST", "/run").body_template("a: [unclosed");
+        endpoint.body_format = BodyFormat::Yaml;
+
+        let request = builder(&client, &provider, &endpoint);
+        assert!(matches!(
+            request.build_request(),
+            Err(CallixError::InvalidBody(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn body_bytes_are_sent_verbatim() {
+        let server = MockServer::start(vec![Reply::ok("ok")]).await;
+        let endpoint = EndpointConfig::new("POST", "/upload").body_template("{{ignored}}");
+        let callix = mock_client(CallixBuilder::new(), &server, endpoint);
+        let payload = vec![0x08, 0x96, 0x01, 0x00, 0xff];
+
+        let request = callix.request("mock", "call").unwrap();
+        request.body_bytes(payload.clone()).send().await.unwrap();
+        let request = callix.request("mock", "call").unwrap();
+        request
+            .body_bytes(payload.clone())
+            .content_type("application/x-protobuf")
+            .send()
+            .await
+            .unwrap();
+
+        let sent = server.requests();
+        assert_eq!(sent[0].body, payload);
+        assert_eq!(
+            sent[0].header("content-type"),
+            Some("application/octet-stream")
+        );
+        assert_eq!(sent[1].body, payload);
+        assert_eq!(
+            sent[1].header("content-type"),
+            Some("application/x-protobuf")
+        );
+    }
+
+    #[cfg(feature = "stream")]
+    fn upload_chunks() -> impl TryStream<Ok = Vec<u8>, Error = std::io::Error> + Send + 'static {
+        let chunks = ["hello", " chunked", " world"];
+        futures_util::stream::iter(chunks.map(|chunk| Ok(chunk.as_bytes().to_vec())))
+    }
+
+    #[cfg(feature = "stream")]
+    #[tokio::test]
+    async fn body_stream_uploads_with_chunked_encoding() {
+        let server = MockServer::start(vec![Reply::ok("stored")]).await;
+        let endpoint = EndpointConfig::new("PUT", "/upload").body_template(r#"{"ignored": 1}"#);
+        let callix = mock_client(CallixBuilder::new(), &server, endpoint);
+
+        let request = callix.request("mock", "call").unwrap();
+        let response = request.body_stream(upload_chunks()).send().await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "stored");
+
+        let sent = &server.requests()[0];
+        assert_eq!(sent.header("transfer-encoding"), Some("chunked"));
+        assert_eq!(sent.header("content-length"), None);
+        assert_eq!(sent.body_text(), "hello chunked world");
+    }
+
+    #[cfg(feature = "stream")]
+    #[tokio::test]
+    async fn body_stream_requests_are_not_retried() {
+        let server = MockServer::start(vec![Reply::new(503, &[], "busy")]).await;
+        let builder = CallixBuilder::new()
+            .retries(2)
+            .retry_delay(Duration::ZERO)
+            .retry_on_status(&[503]);
+        let callix = mock_client(builder, &server, EndpointConfig::new("PUT", "/upload"));
+
+        let request = callix.request("mock", "call").unwrap();
+        let response = request.body_stream(upload_chunks()).send().await.unwrap();
+        assert_eq!(response.status(), 503);
+        assert_eq!(server.hits(), 1);
+    }
+
+    #[tokio::test]
+    async fn endpoints_inherit_the_provider_default_body_type() {
+        let server = MockServer::start(vec![Reply::ok("ok")]).await;
+        let body = json!({"grant_type": "client_credentials", "scope": "{{scope}}"});
+        let mut json_endpoint = EndpointConfig::new("POST", "/json").body(body.clone());
+        json_endpoint.body_type = Some(BodyType::Json);
+        let provider = ProviderConfig {
+            default_body_type: Some(BodyType::Form),
+            ..ProviderConfig::new(&server.url)
+        }
+        .endpoint("token", EndpointConfig::new("POST", "/token").body(body))
+        .endpoint("json", json_endpoint);
+        let mut callix = CallixBuilder::new().build().unwrap();
+        callix.register_provider("oauth", provider).unwrap();
+
+        for endpoint in ["token", "json"] {
+            let request = callix.request("oauth", endpoint).unwrap();
+            request.var("scope", "read write").send().await.unwrap();
+        }
+
+        let sent = server.requests();
+        assert_eq!(
+            sent[0].header("content-type"),
+            Some("application/x-www-form-urlencoded")
+        );
+        let mut fields: Vec<_> = sent[0].body_text().split('&').map(String::from).collect();
+        fields.sort();
+        assert_eq!(
+            fields,
+            ["grant_type=client_credentials", "scope=read+write"]
+        );
+
+        assert_eq!(sent[1].header("content-type"), Some("application/json"));
+        assert_eq!(
+            serde_json::from_slice::<Value>(&sent[1].body).unwrap(),
+            json!({"grant_type": "client_credentials", "scope": "read write"})
+        );
+    }
+
+    #[tokio::test]
+    async fn empty_body_is_sent_distinctly_from_no_body() {
+        let server = MockServer::start(vec![Reply::ok("ok")]).await;
+        let endpoint = EndpointConfig::new("POST", "/empty").empty_body();
+        let mut callix = mock_client(CallixBuilder::new(), &server, endpoint);
+        callix
+            .register_endpoint("mock", "bare", EndpointConfig::new("POST", "/bare"))
+            .unwrap();
+
+        for endpoint in ["call", "bare"] {
+            let request = callix.request("mock", endpoint).unwrap();
+            request.send().await.unwrap();
+        }
+
+        let sent = server.requests();
+        assert_eq!(sent[0].body_text(), "{}");
+        assert_eq!(sent[0].header("content-type"), Some("application/json"));
+        assert!(sent[1].body.is_empty());
+        assert_eq!(sent[1].header("content-type"), None);
+    }
+
+    const USER_QUERY: &str = "query($id: ID!) { user(id: $id) { {{fields}} } }";
+
+    #[tokio::test]
+    async fn graphql_endpoints_send_the_query_and_variables_envelope() {
+        let server = MockServer::start(vec![Reply::json(200, r#"{"data":{}}"#)]).await;
+        let mut endpoint = EndpointConfig::new("POST", "/graphql");
+        endpoint.graphql = Some(GraphqlConfig {
+            query: USER_QUERY.to_string(),
+            variables: Some(json!({"id": "{{id}}", "first": "{{first}}"})),
+        });
+        let callix = mock_client(CallixBuilder::new(), &server, endpoint);
+
+        callix
+            .request("mock", "call")
+            .unwrap()
+            .var("fields", "name email")
+            .var("id", "u-1")
+            .var("first", 10)
+            .send()
+            .await
+            .unwrap();
+
+        let sent = &server.requests()[0];
+        assert_eq!(sent.header("content-type"), Some("application/json"));
+        let body: Value = serde_json::from_slice(&sent.body).unwrap();
+        assert_eq!(
+            body,
+            json!({
+                "query": "query($id: ID!) { user(id: $id) { name email } }",
+                "variables": {"id": "u-1", "first": 10},
+            })
+        );
+    }
+
+    #[test]
+    fn graphql_without_variables_sends_only_the_query() {
+        let client = Client::new();
+        let provider = ProviderConfig::new("https://api.test");
+        let endpoint = EndpointConfig::new("POST", "/graphql")
+            .body_template("{{ignored}}")
+            .graphql(USER_QUERY);
+
+        let request = builder(&client, &provider, &endpoint)
+            .var("fields", "id")
+            .var("token", "secret");
+        assert_eq!(
+            sent_body(&request),
+            json!({"query": "query($id: ID!) { user(id: $id) { id } }"})
+        );
+    }
+
+    #[cfg(feature = "tracing")]
+    #[derive(Clone, Default)]
+    struct SpanCapture {
+        fields: Arc<Mutex<Vec<(String, String)>>>,
+    }
+
+    #[cfg(feature = "tracing")]
+    impl tracing::field::Visit for SpanCapture {
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+            self.record_str(field, &format!("{:?}", value));
+        }
+
+        fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+            self.fields
+                .lock()
+                .unwrap()
+                .push((field.name().to_string(), value.to_string()));
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    impl tracing::Subscriber for SpanCapture {
+        fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            span.record(&mut self.clone());
+            tracing::span::Id::from_u64(1)
+        }
+
+        fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record<'_>) {}
+
+        fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+
+        fn event(&self, _: &tracing::Event<'_>) {}
+
+        fn enter(&self, _: &tracing::span::Id) {}
+
+        fn exit(&self, _: &tracing::span::Id) {}
+    }
+
+    #[cfg(feature = "tracing")]
+    #[tokio::test]
+    async fn trace_fields_are_attached_to_the_request_span() {
+        let server = MockServer::start(vec![Reply::ok("ok")]).await;
+        let fields = HashMap::from([
+            ("service".to_string(), "payments".to_string()),
+            ("team".to_string(), "core".to_string()),
+        ]);
+        let builder = CallixBuilder::new().trace_fields(fields);
+        let callix = mock_client(builder, &server, EndpointConfig::new("GET", "/"));
+
+        let capture = SpanCapture::default();
+        let _guard = tracing::subscriber::set_default(capture.clone());
+        callix
+            .request("mock", "call")
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+
+        let recorded = capture.fields.lock().unwrap().clone();
+        let field = |name: &str| {
+            recorded
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str())
+        };
+        assert_eq!(field("fields"), Some("service=payments,team=core"));
+        assert_eq!(field("provider"), Some("mock"));
+        assert_eq!(field("endpoint"), Some("call"));
+        assert_eq!(field("method"), Some("GET"));
+    }
+
+    #[tokio::test]
+    async fn rate_limited_retries_wait_until_the_reset() {
+        let server = MockServer::start(vec![
+            Reply::new(429, &[("x-ratelimit-reset", "7")], "slow down"),
+            Reply::ok("ok"),
+        ])
+        .await;
+        let sleeper = RecordingSleeper::default();
+        let builder = CallixBuilder::new()
+            .retries(1)
+            .retry_delay(Duration::from_millis(1))
+            .retry_on_status(&[429])
+            .sleeper(sleeper.clone());
+        let callix = mock_client(builder, &server, EndpointConfig::new("GET", "/"));
+
+        let response = callix
+            .request("mock", "call")
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(sleeper.delays(), [Duration::from_secs(7)]);
+    }
+
+    #[test]
+    fn dry_run_curl_renders_a_quoted_command() {
+        let client = Client::new();
+        let provider =
+            ProviderConfig::new("https://api.test").header("Authorization", "Bearer {{token}}");
+        let endpoint = EndpointConfig::new("POST", "/notes")
+            .header("Content-Type", "application/json")
+            .body_template(r#"{"text": "{{text}}"}"#);
+
+        let request = builder(&client, &provider, &endpoint)
+            .var("token", "t-1")
+            .var("text", "it's here");
+
+        assert_eq!(
+            request.dry_run_curl(false).unwrap(),
+            "curl -X POST 'https://api.test/notes' \\\n  \
+             -H 'authorization: Bearer t-1' \\\n  \
+             -H 'content-type: application/json' \\\n  \
+             --data-raw '{\"text\": \"it'\\''s here\"}'"
+        );
+        assert!(
+            request
+                .dry_run_curl(true)
+                .unwrap()
+                .contains("-H 'authorization: [REDACTED]'")
+        );
+    }
+
+    #[tokio::test]
+    async fn secret_variables_are_sent_but_redacted_in_curl_output() {
+        let server = MockServer::start(vec![Reply::ok("ok")]).await;
+        let endpoint = EndpointConfig::new("POST", "/login")
+            .query_param("key", "{{key}}")
+            .body_template(r#"{"key": "{{key}}"}"#);
+        let callix = mock_client(CallixBuilder::new(), &server, endpoint);
+
+        let request = callix
+            .request("mock", "call")
+            .unwrap()
+            .var_secret("key", "s3 cret&x");
+        let curl = request.dry_run_curl(false).unwrap();
+        request.send().await.unwrap();
+
+        assert!(!curl.contains("s3"), "{}", curl);
+        assert!(curl.contains("key=[REDACTED]"), "{}", curl);
+        assert!(curl.contains(r#"{"key": "[REDACTED]"}"#), "{}", curl);
+
+        let sent = &server.requests()[0];
+        assert_eq!(sent.target, "/login?key=s3+cret%26x");
+        assert_eq!(sent.body_text(), r#"{"key": "s3 cret&x"}"#);
+    }
+
+    #[tokio::test]
+    async fn transport_errors_drop_urls_that_contain_secrets() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        let mut callix = CallixBuilder::new().retries(0).build().unwrap();
+        let endpoint = EndpointConfig::new("GET", "/").query_param("key", "{{key}}");
+        callix
+            .register_provider(
+                "down",
+                ProviderConfig::new(base_url).endpoint("call", endpoint),
+            )
+            .unwrap();
+
+        let result = callix
+            .request("down", "call")
+            .unwrap()
+            .var_secret("key", "s3cret")
+            .send()
+            .await;
+        match result.err().unwrap() {
+            CallixError::HttpError(e) => {
+                assert!(e.url().is_none());
+                assert!(!e.to_string().contains("s3cret"));
+            }
+            other => panic!("expected a transport error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn prepared_requests_redact_secrets_in_debug_output() {
+        let client = Client::new();
+        let provider = ProviderConfig::new("https://api.test")
+            .header("Authorization", "Bearer t-12345")
+            .header("api-key", "k-12345");
+        let endpoint = EndpointConfig::new("POST", "/users/{{user}}")
+            .body_template(r#"{"password": "{{password}}"}"#);
+
+        let request = RequestBuilder::new(&client, &provider, &endpoint, 0, Duration::ZERO)
+            .var_secret("user", "ann-secret")
+            .var_secret("password", "hunter22");
+
+        let debug = format!("{:?}", request.build_request().unwrap());
+        for secret in ["t-12345", "k-12345", "ann-secret", "hunter22"] {
+            assert!(!debug.contains(secret), "{} leaked in {}", secret, debug);
+        }
+        assert!(debug.contains("https://api.test/users/[REDACTED]"));
+    }
+
+    #[test]
+    fn redact_error_covers_every_message_variant() {
+        let client = Client::new();
+        let provider = ProviderConfig::new("https://api.test");
+        let endpoint = EndpointConfig::new("GET", "/");
+        let request = RequestBuilder::new(&client, &provider, &endpoint, 0, Duration::ZERO)
+            .var_secret("key", "s3cret");
+
+        let errors = [
+            CallixError::InvalidUrl("https://api.test/?key=s3cret".to_string()),
+            CallixError::ApiError {
+                status: 401,
+                body: "bad key s3cret".to_string(),
+            },
+            CallixError::InvalidBody("near 's3cret'".to_string()),
+            CallixError::ValidationError("s3cret is malformed".to_string()),
+            CallixError::InvalidVariable {
+                name: "key".to_string(),
+                reason: "got 's3cret'".to_string(),
+            },
+            CallixError::IoError(std::io::Error::other("s3cret")),
+        ];
+        for error in errors {
+            let redacted = request.redact_error(error).to_string();
+            assert!(!redacted.contains("s3cret"), "leaked in {}", redacted);
+            assert!(redacted.contains(REDACTED));
+        }
+    }
+
+    #[test]
+    fn short_secrets_do_not_rewrite_unrelated_text() {
+        let client = Client::new();
+        let provider = ProviderConfig::new("https://api.test");
+        let endpoint = EndpointConfig::new("GET", "/v1/items");
+        let request = RequestBuilder::new(&client, &provider, &endpoint, 0, Duration::ZERO)
+            .var_secret("flag", "1");
+
+        assert_eq!(request.explain().unwrap().url, "https://api.test/v1/items");
+    }
+
+    #[tokio::test]
+    async fn retry_delay_fn_sees_each_attempt_and_error() {
+        let server = MockServer::start(vec![
+            Reply::new(503, &[], "busy"),
+            Reply::new(503, &[], "busy"),
+            Reply::ok("ok"),
+        ])
+        .await;
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let sleeper = RecordingSleeper::default();
+        let recorded = Arc::clone(&calls);
+        let builder = CallixBuilder::new()
+            .retries(3)
+            .retry_on_status(&[503])
+            .sleeper(sleeper.clone())
+            .retry_delay_fn(move |attempt, error| {
+                recorded.lock().unwrap().push((attempt, error.is_some()));
+                Duration::from_millis(100 * u64::from(attempt))
+            });
+        let callix = mock_client(builder, &server, EndpointConfig::new("GET", "/"));
+
+        let response = callix
+            .request("mock", "call")
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(*calls.lock().unwrap(), [(1, false), (2, false)]);
+        assert_eq!(
+            sleeper.delays(),
+            [Duration::from_millis(100), Duration::from_millis(200)]
+        );
+    }
+
+    #[tokio::test]
+    async fn retry_delay_fn_receives_transport_errors() {
+        let server = MockServer::start(vec![Reply::ok("late").delay(Duration::from_secs(5))]).await;
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&errors);
+        let builder = CallixBuilder::new()
+            .timeout(Duration::from_millis(50))
+            .retries(1)
+            .sleeper(RecordingSleeper::default())
+            .retry_delay_fn(move |attempt, error| {
+                let timed_out = matches!(error, Some(CallixError::TimeoutError));
+                recorded.lock().unwrap().push((attempt, timed_out));
+                Duration::ZERO
+            });
+        let callix = mock_client(builder, &server, EndpointConfig::new("GET", "/"));
+
+        assert!(
+            callix
+                .request("mock", "call")
+                .unwrap()
+                .send()
+                .await
+                .is_err()
+        );
+        assert_eq!(*errors.lock().unwrap(), [(1, true)]);
     }
 }
@@ -1,15 +1,102 @@
-use crate::error::Result;
-use reqwest::Response;
+use crate::error::{CallixError, Result, TypedError};
+#[cfg(feature = "stream")]
+use futures_util::{Stream, StreamExt, stream};
+use reqwest::header::{CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE};
+use reqwest::{Response, ResponseBuilderExt};
 use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::fs::{self, File};
+use tokio::io::AsyncWriteExt;
+
+const ERROR_BODY_LIMIT: usize = 64 * 1024;
+const TRUNCATED_MARKER: &str = "... [truncated]";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    pub attributes: Vec<(String, Option<String>)>,
+}
+
+impl Cookie {
+    pub fn attribute(&self, name: &str) -> Option<Option<&str>> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_deref())
+    }
+}
 
 pub struct CallixResponse {
     inner: Response,
+    elapsed: Duration,
+    request_id: Option<String>,
+    max_size: Option<usize>,
+    success_statuses: Option<Vec<u16>>,
 }
 
 impl CallixResponse {
     #[inline]
     pub fn new(response: Response) -> Self {
-        Self { inner: response }
+        Self {
+            inner: response,
+            elapsed: Duration::ZERO,
+            request_id: None,
+            max_size: None,
+            success_statuses: None,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn with_elapsed(mut self, elapsed: Duration) -> Self {
+        self.elapsed = elapsed;
+        self
+    }
+
+    #[inline]
+    pub(crate) fn with_request_id(mut self, request_id: Option<String>) -> Self {
+        self.request_id = request_id;
+        self
+    }
+
+    #[inline]
+    pub(crate) fn with_max_size(mut self, max_size: Option<usize>) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    #[inline]
+    pub(crate) fn with_success_statuses(mut self, statuses: Option<Vec<u16>>) -> Self {
+        self.success_statuses = statuses;
+        self
+    }
+
+    pub(crate) async fn buffer(self) -> Result<(Self, Vec<u8>)> {
+        let mut builder = http::Response::builder()
+            .status(self.inner.status())
+            .version(self.inner.version())
+            .url(self.inner.url().clone());
+        if let Some(headers) = builder.headers_mut() {
+            *headers = self.inner.headers().clone();
+        }
+
+        let elapsed = self.elapsed;
+        let request_id = self.request_id.clone();
+        let max_size = self.max_size;
+        let success_statuses = self.success_statuses.clone();
+        let bytes = self.bytes().await?;
+        let response = builder
+            .body(bytes.clone())
+            .map_err(|e| CallixError::InvalidBody(e.to_string()))?;
+
+        let response = Self::new(Response::from(response))
+            .with_elapsed(elapsed)
+            .with_request_id(request_id)
+            .with_max_size(max_size)
+            .with_success_statuses(success_statuses);
+        Ok((response, bytes))
     }
 
     #[inline]
@@ -19,7 +106,40 @@ impl CallixResponse {
 
     #[inline]
     pub fn is_success(&self) -> bool {
-        self.inner.status().is_success()
+        match &self.success_statuses {
+            Some(statuses) => statuses.contains(&self.status()),
+            None => self.inner.status().is_success(),
+        }
+    }
+
+    pub async fn error_for_status(self) -> Result<Self> {
+        if self.is_success() {
+            return Ok(self);
+        }
+
+        let status = self.status();
+        let body = self.error_body().await;
+        Err(CallixError::ApiError { status, body })
+    }
+
+    pub async fn json_typed_error_for_status<T: DeserializeOwned, E: DeserializeOwned>(
+        self,
+    ) -> std::result::Result<T, TypedError<E>> {
+        if self.is_success() {
+            return Ok(self.json().await?);
+        }
+
+        let status = self.status();
+        let body = self.error_body().await;
+        match serde_json::from_str(&body) {
+            Ok(error) => Err(TypedError::Api { status, error }),
+            Err(_) => Err(CallixError::ApiError { status, body }.into()),
+        }
+    }
+
+    #[inline]
+    pub fn url(&self) -> &reqwest::Url {
+        self.inner.url()
     }
 
     #[inline]
@@ -27,18 +147,1080 @@ impl CallixResponse {
         self.inner.headers()
     }
 
+    pub fn header_all(&self, name: &str) -> Vec<&str> {
+        self.inner
+            .headers()
+            .get_all(name)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .collect()
+    }
+
+    pub fn cookies(&self) -> Vec<Cookie> {
+        self.header_all("set-cookie")
+            .into_iter()
+            .filter_map(parse_cookie)
+            .collect()
+    }
+
+    pub fn next_link(&self) -> Option<reqwest::Url> {
+        self.header_all("link")
+            .into_iter()
+            .find_map(|value| find_link(value, "next"))
+            .and_then(|target| self.inner.url().join(target).ok())
+    }
+
     #[inline]
-    pub async fn text(self) -> Result<String> {
-        Ok(self.inner.text().await?)
+    pub fn content_length(&self) -> Option<u64> {
+        self.inner
+            .headers()
+            .get(CONTENT_LENGTH)?
+            .to_str()
+            .ok()?
+            .parse()
+            .ok()
     }
 
     #[inline]
-    pub async fn json<T: DeserializeOwned>(self) -> Result<T> {
-        Ok(self.inner.json().await?)
+    pub fn content_encoding(&self) -> Option<&str> {
+        self.inner.headers().get(CONTENT_ENCODING)?.to_str().ok()
+    }
+
+    pub fn content_type(&self) -> Option<&str> {
+        self.inner.headers().get(CONTENT_TYPE)?.to_str().ok()
+    }
+
+    pub fn expect_content_type(self, expected: &str) -> Result<Self> {
+        let matches = self.content_type().is_some_and(|content_type| {
+            let media_type = content_type.split(';').next().unwrap_or_default();
+            media_type.trim().eq_ignore_ascii_case(expected.trim())
+        });
+
+        if matches {
+            Ok(self)
+        } else {
+            Err(CallixError::UnexpectedContentType {
+                expected: expected.to_string(),
+                actual: self.content_type().map(String::from),
+            })
+        }
+    }
+
+    #[inline]
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
     }
 
     #[inline]
+    pub fn request_id(&self) -> Option<&str> {
+        self.request_id.as_deref()
+    }
+
+    pub async fn text(self) -> Result<String> {
+        if self.max_size.is_none() {
+            return Ok(self.inner.text().await?);
+        }
+        let (response, _) = self.buffer().await?;
+        Ok(response.inner.text().await?)
+    }
+
+    pub async fn text_utf8(self) -> Result<String> {
+        let bytes = self.bytes().await?;
+        String::from_utf8(bytes).map_err(|e| CallixError::InvalidUtf8(e.utf8_error()))
+    }
+
+    pub async fn text_lossy(self) -> Result<String> {
+        let bytes = self.bytes().await?;
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    pub async fn json<T: DeserializeOwned>(self) -> Result<T> {
+        let bytes = self.bytes().await?;
+        serde_json::from_slice(&bytes).map_err(CallixError::JsonError)
+    }
+
+    pub async fn json_path<T: DeserializeOwned>(self, pointer: &str) -> Result<T> {
+        let value: Value = self.json().await?;
+        let target = value
+            .pointer(pointer)
+            .ok_or_else(|| CallixError::JsonPointerNotFound(pointer.to_string()))?;
+
+        T::deserialize(target).map_err(CallixError::JsonError)
+    }
+
     pub async fn bytes(self) -> Result<Vec<u8>> {
-        Ok(self.inner.bytes().await?.to_vec())
+        match self.max_size {
+            Some(limit) => self.read_limited(limit).await,
+            None => Ok(self.inner.bytes().await?.to_vec()),
+        }
+    }
+
+    pub async fn json_with_raw<T: DeserializeOwned>(self) -> Result<(T, String)> {
+        let raw = self.text_utf8().await?;
+        let value = serde_json::from_str(&raw).map_err(CallixError::JsonError)?;
+        Ok((value, raw))
+    }
+
+    pub async fn deserialize_with<T>(self, f: impl FnOnce(&[u8]) -> Result<T>) -> Result<T> {
+        let bytes = self.bytes().await?;
+        f(&bytes)
+    }
+
+    pub async fn save_to_file(self, path: impl AsRef<Path>) -> Result<u64> {
+        if let Some(limit) = self.max_size
+            && self.content_length().is_some_and(|len| len > limit as u64)
+        {
+            return Err(CallixError::ResponseTooLarge(limit));
+        }
+
+        let path = path.as_ref();
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".part");
+        let partial = PathBuf::from(partial);
+
+        let result = match self.write_body(&partial).await {
+            Ok(written) => fs::rename(&partial, path)
+                .await
+                .map(|_| written)
+                .map_err(CallixError::from),
+            Err(e) => Err(e),
+        };
+        if result.is_err() {
+            let _ = fs::remove_file(&partial).await;
+        }
+        result
+    }
+
+    async fn write_body(mut self, path: &Path) -> Result<u64> {
+        let mut file = File::create(path).await?;
+        let mut written = 0u64;
+
+        while let Some(chunk) = self.inner.chunk().await? {
+            written += chunk.len() as u64;
+            if let Some(limit) = self.max_size.filter(|&limit| written > limit as u64) {
+                return Err(CallixError::ResponseTooLarge(limit));
+            }
+            file.write_all(&chunk).await?;
+        }
+
+        file.flush().await?;
+        Ok(written)
+    }
+
+    async fn read_limited(mut self, limit: usize) -> Result<Vec<u8>> {
+        if self.content_length().is_some_and(|len| len > limit as u64) {
+            return Err(CallixError::ResponseTooLarge(limit));
+        }
+
+        let mut body = Vec::new();
+        while let Some(chunk) = self.inner.chunk().await? {
+            if body.len() + chunk.len() > limit {
+                return Err(CallixError::ResponseTooLarge(limit));
+            }
+            body.extend_from_slice(&chunk);
+        }
+        Ok(body)
+    }
+
+    pub async fn error_body(mut self) -> String {
+        let mut body = Vec::new();
+        let mut truncated = false;
+
+        while let Ok(Some(chunk)) = self.inner.chunk().await {
+            let remaining = ERROR_BODY_LIMIT - body.len();
+            if chunk.len() > remaining {
+                body.extend_from_slice(&chunk[..remaining]);
+                truncated = true;
+                break;
+            }
+            body.extend_from_slice(&chunk);
+        }
+
+        let mut text = String::from_utf8_lossy(&body).into_owned();
+        if truncated {
+            text.push_str(TRUNCATED_MARKER);
+        }
+        text
+    }
+
+    pub async fn collect_sse_text(mut self) -> Result<(String, usize)> {
+        let mut text = String::new();
+        let mut chunks = 0;
+        let mut buffer = Vec::new();
+        let mut received = 0;
+
+        loop {
+            let next = self.inner.chunk().await?;
+            let done = next.is_none();
+            if let Some(bytes) = next {
+                received += bytes.len();
+                if let Some(limit) = self.max_size.filter(|&limit| received > limit) {
+                    return Err(CallixError::ResponseTooLarge(limit));
+                }
+                buffer.extend_from_slice(&bytes);
+            }
+
+            while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=pos).collect();
+                if let Some(content) = parse_sse_delta(&line)? {
+                    text.push_str(&content);
+                    chunks += 1;
+                }
+            }
+
+            if done {
+                if let Some(content) = parse_sse_delta(&buffer)? {
+                    text.push_str(&content);
+                    chunks += 1;
+                }
+                return Ok((text, chunks));
+            }
+        }
+    }
+
+    #[cfg(feature = "stream")]
+    fn body_chunks(self) -> impl Stream<Item = Result<Vec<u8>>> {
+        let limit = self.max_size;
+        let declared_too_large = limit
+            .filter(|&limit| self.content_length().is_some_and(|len| len > limit as u64))
+            .map(|limit| Err(CallixError::ResponseTooLarge(limit)));
+
+        let mut received = 0;
+        let chunks = self.inner.bytes_stream().map(move |chunk| {
+            let chunk = chunk?;
+            received += chunk.len();
+            match limit {
+                Some(limit) if received > limit => Err(CallixError::ResponseTooLarge(limit)),
+                _ => Ok(chunk.to_vec()),
+            }
+        });
+
+        stream::iter(declared_too_large).chain(chunks)
+    }
+
+    #[cfg(feature = "stream")]
+    pub fn json_stream_array<T: DeserializeOwned>(self) -> impl Stream<Item = Result<T>> {
+        let chunks = Box::pin(self.body_chunks());
+
+        stream::unfold(
+            (chunks, ArrayScanner::default(), false),
+            |(mut chunks, mut scanner, mut eof)| async move {
+                loop {
+                    match scanner.next_element() {
+                        Ok(Some(element)) => {
+                            let item =
+                                serde_json::from_slice(&element).map_err(CallixError::JsonError);
+                            return Some((item, (chunks, scanner, eof)));
+                        }
+                        Ok(None) if scanner.finished => return None,
+                        Ok(None) => {}
+                        Err(e) => {
+                            scanner.finished = true;
+                            return Some((Err(e), (chunks, scanner, eof)));
+                        }
+                    }
+
+                    if eof {
+                        scanner.finished = true;
+                        let error = CallixError::InvalidBody("unterminated JSON array".to_string());
+                        return Some((Err(error), (chunks, scanner, eof)));
+                    }
+
+                    match chunks.next().await {
+                        Some(Ok(bytes)) => scanner.buffer.extend_from_slice(&bytes),
+                        Some(Err(e)) => {
+                            scanner.finished = true;
+                            return Some((Err(e), (chunks, scanner, eof)));
+                        }
+                        None => eof = true,
+                    }
+                }
+            },
+        )
+    }
+
+    #[cfg(feature = "stream")]
+    pub fn json_lines<T: DeserializeOwned>(self) -> impl Stream<Item = Result<T>> {
+        let chunks = Box::pin(self.body_chunks());
+
+        stream::unfold(
+            (chunks, Vec::new(), false),
+            |(mut chunks, mut buffer, mut done)| async move {
+                loop {
+                    if let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+                        let line: Vec<u8> = buffer.drain(..=pos).collect();
+                        if let Some(item) = parse_json_line(&line) {
+                            return Some((item, (chunks, buffer, done)));
+                        }
+                        continue;
+                    }
+
+                    if done {
+                        let line = std::mem::take(&mut buffer);
+                        return parse_json_line(&line).map(|item| (item, (chunks, buffer, done)));
+                    }
+
+                    match chunks.next().await {
+                        Some(Ok(bytes)) => buffer.extend_from_slice(&bytes),
+                        Some(Err(e)) => {
+                            buffer.clear();
+                            return Some((Err(e), (chunks, buffer, true)));
+                        }
+                        None => done = true,
+                    }
+                }
+            },
+        )
+    }
+}
+
+fn parse_cookie(header: &str) -> Option<Cookie> {
+    let mut parts = header.split(';');
+    let (name, value) = parts.next()?.split_once('=')?;
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+
+    let attributes = parts
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .map(|part| match part.split_once('=') {
+            Some((key, value)) => (key.trim().to_string(), Some(value.trim().to_string())),
+            None => (part.to_string(), None),
+        })
+        .collect();
+
+    Some(Cookie {
+        name: name.to_string(),
+        value: value.trim().trim_matches('"').to_string(),
+        attributes,
+    })
+}
+
+fn find_link<'a>(header: &'a str, rel: &str) -> Option<&'a str> {
+    let mut rest = header;
+
+    while let Some(start) = rest.find('<') {
+        let end = start + rest[start..].find('>')?;
+        let target = &rest[start + 1..end];
+
+        let params = &rest[end + 1..];
+        let params = &params[..params.find('<').unwrap_or(params.len())];
+        let matches = params.split(';').any(|param| {
+            let Some((key, value)) = param.split_once('=') else {
+                return false;
+            };
+            key.trim().eq_ignore_ascii_case("rel")
+                && value
+                    .trim()
+                    .trim_end_matches(',')
+                    .trim()
+                    .trim_matches('"')
+                    .split_whitespace()
+                    .any(|value| value.eq_ignore_ascii_case(rel))
+        });
+
+        if matches {
+            return Some(target);
+        }
+        rest = &rest[end + 1..];
+    }
+
+    None
+}
+
+fn parse_sse_delta(line: &[u8]) -> Result<Option<String>> {
+    let Some(data) = line.trim_ascii().strip_prefix(b"data:") else {
+        return Ok(None);
+    };
+
+    let data = data.trim_ascii();
+    if data.is_empty() || data == b"[DONE]" {
+        return Ok(None);
+    }
+
+    let event: Value = serde_json::from_slice(data).map_err(CallixError::JsonError)?;
+    Ok(event
+        .pointer("/choices/0/delta/content")
+        .and_then(Value::as_str)
+        .map(str::to_string))
+}
+
+#[cfg(feature = "stream")]
+#[derive(Default)]
+struct ArrayScanner {
+    buffer: Vec<u8>,
+    pos: usize,
+    depth: usize,
+    started: bool,
+    in_string: bool,
+    escaped: bool,
+    finished: bool,
+}
+
+#[cfg(feature = "stream")]
+impl ArrayScanner {
+    fn next_element(&mut self) -> Result<Option<Vec<u8>>> {
+        if self.finished {
+            return Ok(None);
+        }
+
+        if !self.started {
+            let Some(start) = self.buffer.iter().position(|b| !b.is_ascii_whitespace()) else {
+                return Ok(None);
+            };
+            if self.buffer[start] != b'[' {
+                return Err(CallixError::InvalidBody(
+                    "expected a JSON array".to_string(),
+                ));
+            }
+            self.buffer.drain(..=start);
+            self.started = true;
+        }
+
+        while self.pos < self.buffer.len() {
+            let byte = self.buffer[self.pos];
+
+            if self.in_string {
+                match byte {
+                    _ if self.escaped => self.escaped = false,
+                    b'\\' => self.escaped = true,
+                    b'"' => self.in_string = false,
+                    _ => {}
+                }
+            } else {
+                match byte {
+                    b'"' => self.in_string = true,
+                    b'{' | b'[' => self.depth += 1,
+                    b'}' | b']' if self.depth > 0 => self.depth -= 1,
+                    b',' | b']' if self.depth == 0 => {
+                        self.finished = byte == b']';
+                        let element: Vec<u8> = self.buffer.drain(..=self.pos).collect();
+                        self.pos = 0;
+
+                        let element = element[..element.len() - 1].trim_ascii();
+                        if !element.is_empty() {
+                            return Ok(Some(element.to_vec()));
+                        }
+                        if self.finished {
+                            return Ok(None);
+                        }
+                        continue;
+                    }
+                    _ => {}
+                }
+            }
+
+            self.pos += 1;
+        }
+
+        Ok(None)
+    }
+}
+
+#[cfg(feature = "stream")]
+fn parse_json_line<T: DeserializeOwned>(line: &[u8]) -> Option<Result<T>> {
+    let line = line.trim_ascii();
+    if line.is_empty() {
+        return None;
+    }
+    Some(serde_json::from_slice(line).map_err(CallixError::JsonError))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "stream")]
+    use crate::error::CallixError;
+    use crate::test_support::{Reply, TempFile, respond};
+    #[cfg(feature = "stream")]
+    use serde::Deserialize;
+
+    #[cfg(feature = "stream")]
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Record {
+        id: u32,
+    }
+
+    #[tokio::test]
+    async fn content_length_reads_the_declared_header() {
+        let response = respond(Reply::ok("twelve bytes")).await;
+        assert_eq!(response.content_length(), Some(12));
+
+        let response = respond(Reply::chunked(200, &[], &[b"streamed"])).await;
+        assert_eq!(response.content_length(), None);
+    }
+
+    #[tokio::test]
+    async fn text_lossy_replaces_invalid_utf8() {
+        let response = respond(Reply::ok(b"ok \xff\xfe done")).await;
+        assert_eq!(
+            response.text_lossy().await.unwrap(),
+            "ok \u{fffd}\u{fffd} done"
+        );
+    }
+
+    #[cfg(feature = "zstd")]
+    #[tokio::test]
+    async fn decodes_zstd_encoded_bodies() {
+        let json = br#"{"id":7}"#;
+        let mut frame = vec![0x28, 0xb5, 0x2f, 0xfd, 0x20, json.len() as u8];
+        let block_header = 1 | (json.len() as u32) << 3;
+        frame.extend_from_slice(&block_header.to_le_bytes()[..3]);
+        frame.extend_from_slice(json);
+
+        let reply = Reply::new(200, &[("content-encoding", "zstd")], frame);
+        let value: Value = respond(reply).await.json().await.unwrap();
+        assert_eq!(value, serde_json::json!({"id": 7}));
+    }
+
+    fn sse_delta(content: &str) -> String {
+        let event = serde_json::json!({"choices": [{"delta": {"content": content}}]});
+        format!("data: {}\n\n", event)
+    }
+
+    #[tokio::test]
+    async fn collect_sse_text_concatenates_deltas() {
+        let first = sse_delta("Hel");
+        let second = sse_delta("lo");
+        let role_only = "data: {\"choices\":[{\"delta\":{\"role\":\"assistant\"}}]}\n\n";
+        let chunks: &[&[u8]] = &[
+            b": keep-alive\n\n",
+            role_only.as_bytes(),
+            &first.as_bytes()[..10],
+            &first.as_bytes()[10..],
+            second.as_bytes(),
+            b"data: [DONE]\n\n",
+        ];
+        let reply = Reply::chunked(200, &[("content-type", "text/event-stream")], chunks);
+
+        let (text, count) = respond(reply).await.collect_sse_text().await.unwrap();
+        assert_eq!(text, "Hello");
+        assert_eq!(count, 2);
+    }
+
+    #[tokio::test]
+    async fn collect_sse_text_reports_malformed_events_as_json_errors() {
+        let response = respond(Reply::ok("data: {not json}\n\n")).await;
+        assert!(matches!(
+            response.collect_sse_text().await,
+            Err(CallixError::JsonError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn header_all_returns_every_value() {
+        let headers = [("set-cookie", "a=1; Path=/"), ("set-cookie", "b=2")];
+        let response = respond(Reply::new(200, &headers, "")).await;
+
+        assert_eq!(response.header_all("Set-Cookie"), ["a=1; Path=/", "b=2"]);
+        assert!(response.header_all("x-missing").is_empty());
+    }
+
+    #[tokio::test]
+    async fn cookies_parse_every_set_cookie_header() {
+        let headers = [
+            (
+                "set-cookie",
+                "session=abc123; Path=/; HttpOnly; Max-Age=3600",
+            ),
+            ("set-cookie", "theme=\"dark\""),
+            ("set-cookie", "malformed"),
+        ];
+        let response = respond(Reply::new(200, &headers, "")).await;
+
+        let cookies = response.cookies();
+        assert_eq!(cookies.len(), 2);
+        assert_eq!(cookies[0].name, "session");
+        assert_eq!(cookies[0].value, "abc123");
+        assert_eq!(cookies[0].attribute("path"), Some(Some("/")));
+        assert_eq!(cookies[0].attribute("HttpOnly"), Some(None));
+        assert_eq!(cookies[0].attribute("max-age"), Some(Some("3600")));
+        assert_eq!(cookies[0].attribute("secure"), None);
+        assert_eq!(cookies[1].name, "theme");
+        assert_eq!(cookies[1].value, "dark");
+        assert!(cookies[1].attributes.is_empty());
+    }
+
+    #[test]
+    fn parse_cookie_rejects_nameless_pairs() {
+        assert!(parse_cookie("=value; Path=/").is_none());
+        assert_eq!(parse_cookie("empty=").unwrap().value, "");
+    }
+
+    async fn limited(reply: Reply, limit: usize) -> CallixResponse {
+        respond(reply).await.with_max_size(Some(limit))
+    }
+
+    fn too_large<T: std::fmt::Debug>(result: Result<T>, limit: usize) {
+        match result {
+            Err(CallixError::ResponseTooLarge(actual)) => assert_eq!(actual, limit),
+            other => panic!("expected the size limit to trip, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn max_size_rejects_a_declared_oversized_body() {
+        too_large(limited(Reply::ok("0123456789"), 4).await.bytes().await, 4);
+    }
+
+    #[tokio::test]
+    async fn max_size_allows_bodies_up_to_the_limit() {
+        let reply = Reply::chunked(200, &[], &[b"01234", b"56789"]);
+        let text = limited(reply, 10).await.text().await.unwrap();
+        assert_eq!(text, "0123456789");
+    }
+
+    #[tokio::test]
+    async fn max_size_aborts_chunked_bodies_mid_stream() {
+        let chunks: &[&[u8]] = &[b"[1,2,", b"3,4,", b"5,6]"];
+
+        let response = limited(Reply::chunked(200, &[], chunks), 8).await;
+        too_large(response.text().await, 8);
+
+        let response = limited(Reply::chunked(200, &[], chunks), 8).await;
+        too_large(response.json::<Value>().await, 8);
+
+        let response = limited(Reply::chunked(200, &[], chunks), 8).await;
+        too_large(response.buffer().await.map(|(_, body)| body), 8);
+    }
+
+    #[tokio::test]
+    async fn buffer_keeps_the_limit_on_the_replayed_response() {
+        let (response, body) = limited(Reply::ok("abc"), 8).await.buffer().await.unwrap();
+        assert_eq!(body, b"abc");
+        assert_eq!(response.max_size, Some(8));
+        assert_eq!(response.text().await.unwrap(), "abc");
+    }
+
+    #[cfg(feature = "stream")]
+    #[tokio::test]
+    async fn max_size_applies_to_streamed_readers() {
+        let chunks: &[&[u8]] = &[b"{\"id\":1}\n", b"{\"id\":2}\n"];
+        let response = limited(Reply::chunked(200, &[], chunks), 12).await;
+        let items: Vec<Result<Record>> = response.json_lines().collect().await;
+        assert_eq!(items[0].as_ref().unwrap(), &Record { id: 1 });
+        assert!(matches!(items[1], Err(CallixError::ResponseTooLarge(12))));
+
+        let chunks: &[&[u8]] = &[b"[{\"id\":1},", b"{\"id\":2}]"];
+        let response = limited(Reply::chunked(200, &[], chunks), 12).await;
+        let items: Vec<Result<Record>> = response.json_stream_array().collect().await;
+        assert_eq!(items[0].as_ref().unwrap(), &Record { id: 1 });
+        assert!(matches!(items[1], Err(CallixError::ResponseTooLarge(12))));
+
+        let response = limited(Reply::ok("[{\"id\":1}]"), 4).await;
+        let items: Vec<Result<Record>> = response.json_stream_array().collect().await;
+        assert!(matches!(items[..], [Err(CallixError::ResponseTooLarge(4))]));
+    }
+
+    #[tokio::test]
+    async fn text_utf8_rejects_invalid_utf8() {
+        let response = respond(Reply::ok(b"ok \xff")).await;
+        assert!(matches!(
+            response.text_utf8().await,
+            Err(CallixError::InvalidUtf8(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn text_decodes_the_declared_charset() {
+        let latin1 = [("content-type", "text/plain; charset=iso-8859-1")];
+        let response = respond(Reply::new(200, &latin1, b"caf\xe9")).await;
+        assert_eq!(response.text().await.unwrap(), "caf\u{e9}");
+
+        let response = limited(Reply::new(200, &latin1, b"caf\xe9"), 8).await;
+        assert_eq!(response.text().await.unwrap(), "caf\u{e9}");
+
+        let response = respond(Reply::ok(b"ok \xff")).await;
+        assert_eq!(response.text().await.unwrap(), "ok \u{fffd}");
+    }
+
+    fn partial_path(target: &Path) -> PathBuf {
+        let mut partial = target.as_os_str().to_owned();
+        partial.push(".part");
+        PathBuf::from(partial)
+    }
+
+    #[tokio::test]
+    async fn save_to_file_writes_the_body_and_counts_bytes() {
+        let target = TempFile::new("bin", "old contents");
+        let reply = Reply::chunked(200, &[], &[b"\x00\x01binary", b" data"]);
+
+        let written = respond(reply)
+            .await
+            .save_to_file(target.path())
+            .await
+            .unwrap();
+        assert_eq!(written, 13);
+        assert_eq!(
+            std::fs::read(target.path()).unwrap(),
+            b"\x00\x01binary data"
+        );
+        assert!(!partial_path(target.path()).exists());
+    }
+
+    #[tokio::test]
+    async fn failed_downloads_leave_the_target_untouched() {
+        let target = TempFile::new("bin", "old contents");
+        let reply = Reply::chunked(200, &[], &[b"0123", b"4567", b"89"]);
+
+        let response = limited(reply, 6).await;
+        too_large(response.save_to_file(target.path()).await, 6);
+        assert_eq!(std::fs::read(target.path()).unwrap(), b"old contents");
+        assert!(!partial_path(target.path()).exists());
+    }
+
+    #[test]
+    fn find_link_matches_the_requested_rel() {
+        let header = r#"<https://api.test/items?page=1>; rel="prev", <https://api.test/items?page=3>; rel="next last""#;
+        assert_eq!(
+            find_link(header, "next"),
+            Some("https://api.test/items?page=3")
+        );
+        assert_eq!(
+            find_link(header, "prev"),
+            Some("https://api.test/items?page=1")
+        );
+        assert_eq!(find_link(header, "first"), None);
+        assert_eq!(
+            find_link("</b>; title=next, </c>; REL=next", "next"),
+            Some("/c")
+        );
+    }
+
+    #[tokio::test]
+    async fn next_link_resolves_against_the_response_url() {
+        let headers = [("link", r#"</items?page=2>; rel="next""#)];
+        let response = respond(Reply::new(200, &headers, "[]")).await;
+
+        let next = response.next_link().unwrap();
+        assert_eq!(next.path(), "/items");
+        assert_eq!(next.query(), Some("page=2"));
+
+        let response = respond(Reply::ok("[]")).await;
+        assert_eq!(response.next_link(), None);
+    }
+
+    #[tokio::test]
+    async fn json_path_extracts_a_nested_value() {
+        let body = r#"{"choices":[{"message":{"content":"hi"}}]}"#;
+        let response = respond(Reply::json(200, body)).await;
+
+        let content: String = response
+            .json_path("/choices/0/message/content")
+            .await
+            .unwrap();
+        assert_eq!(content, "hi");
+    }
+
+    #[tokio::test]
+    async fn json_path_reports_a_missing_pointer() {
+        let response = respond(Reply::json(200, r#"{"choices":[]}"#)).await;
+
+        match response.json_path::<String>("/choices/0/text").await {
+            Err(CallixError::JsonPointerNotFound(pointer)) => {
+                assert_eq!(pointer, "/choices/0/text");
+            }
+            other => panic!("expected a missing pointer error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn expect_content_type_rejects_an_html_error_page() {
+        let reply = Reply::new(200, &[("content-type", "text/html")], "<html></html>");
+        let response = respond(reply).await;
+
+        match response.expect_content_type("application/json") {
+            Err(CallixError::UnexpectedContentType { expected, actual }) => {
+                assert_eq!(expected, "application/json");
+                assert_eq!(actual.as_deref(), Some("text/html"));
+            }
+            Err(other) => panic!("expected a content type error, got {:?}", other),
+            Ok(_) => panic!("expected a content type error"),
+        }
+    }
+
+    #[tokio::test]
+    async fn expect_content_type_ignores_parameters_and_case() {
+        let reply = Reply::new(
+            200,
+            &[("content-type", "Application/JSON; charset=utf-8")],
+            r#"{"ok":true}"#,
+        );
+        let response = respond(reply).await;
+
+        let body: serde_json::Value = response
+            .expect_content_type("application/json")
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(body, serde_json::json!({"ok": true}));
+    }
+
+    #[tokio::test]
+    async fn expect_content_type_reports_a_missing_header() {
+        let response = respond(Reply::ok("plain")).await;
+
+        assert!(matches!(
+            response.expect_content_type("application/json"),
+            Err(CallixError::UnexpectedContentType { actual: None, .. })
+        ));
+    }
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct Completion {
+        id: String,
+        #[serde(default)]
+        finish_reason: Option<String>,
+    }
+
+    #[tokio::test]
+    async fn json_with_raw_returns_the_typed_value_and_the_body() {
+        let body = r#"{"id":"c-1","model":"m-2"}"#;
+        let response = respond(Reply::json(200, body)).await;
+
+        let (completion, raw) = response.json_with_raw::<Completion>().await.unwrap();
+        assert_eq!(
+            completion,
+            Completion {
+                id: "c-1".to_string(),
+                finish_reason: None,
+            }
+        );
+        assert_eq!(raw, body);
+    }
+
+    #[tokio::test]
+    async fn json_with_raw_reports_mismatched_bodies() {
+        let response = respond(Reply::json(200, r#"{"model":"m-2"}"#)).await;
+
+        assert!(matches!(
+            response.json_with_raw::<Completion>().await,
+            Err(CallixError::JsonError(_))
+        ));
+    }
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct ApiFailure {
+        message: String,
+    }
+
+    async fn typed(reply: Reply) -> std::result::Result<Completion, TypedError<ApiFailure>> {
+        respond(reply).await.json_typed_error_for_status().await
+    }
+
+    #[tokio::test]
+    async fn json_typed_error_for_status_parses_successes() {
+        let completion = typed(Reply::json(200, r#"{"id":"c-1"}"#)).await.unwrap();
+        assert_eq!(completion.id, "c-1");
+    }
+
+    #[tokio::test]
+    async fn json_typed_error_for_status_parses_typed_failures() {
+        match typed(Reply::json(404, r#"{"message":"no such model"}"#)).await {
+            Err(TypedError::Api { status, error }) => {
+                assert_eq!(status, 404);
+                assert_eq!(error.message, "no such model");
+            }
+            other => panic!("expected a typed API error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn json_typed_error_for_status_falls_back_to_the_raw_body() {
+        let reply = Reply::new(
+            502,
+            &[("content-type", "text/html")],
+            "<h1>Bad Gateway</h1>",
+        );
+
+        match typed(reply).await {
+            Err(TypedError::Callix(CallixError::ApiError { status, body })) => {
+                assert_eq!(status, 502);
+                assert_eq!(body, "<h1>Bad Gateway</h1>");
+            }
+            other => panic!("expected a raw API error, got {:?}", other),
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Score {
+        name: String,
+        points: u32,
+    }
+
+    fn parse_scores(bytes: &[u8]) -> Result<Vec<Score>> {
+        let text = std::str::from_utf8(bytes).map_err(CallixError::InvalidUtf8)?;
+        text.lines()
+            .skip(1)
+            .map(|line| {
+                let (name, points) = line
+                    .split_once(',')
+                    .ok_or_else(|| CallixError::InvalidBody(line.to_string()))?;
+                let points = points
+                    .parse()
+                    .map_err(|_| CallixError::InvalidBody(line.to_string()))?;
+                Ok(Score {
+                    name: name.to_string(),
+                    points,
+                })
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn deserialize_with_hands_the_body_to_the_closure() {
+        let response = respond(Reply::ok("name,points\nann,3\nbob,5\n")).await;
+
+        let scores = response.deserialize_with(parse_scores).await.unwrap();
+        assert_eq!(
+            scores,
+            [
+                Score {
+                    name: "ann".to_string(),
+                    points: 3,
+                },
+                Score {
+                    name: "bob".to_string(),
+                    points: 5,
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn deserialize_with_propagates_closure_errors() {
+        let response = respond(Reply::ok("name,points\nann,many\n")).await;
+
+        match response.deserialize_with(parse_scores).await {
+            Err(CallixError::InvalidBody(line)) => assert_eq!(line, "ann,many"),
+            other => panic!("expected an invalid body error, got {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "stream")]
+    #[tokio::test]
+    async fn json_lines_yields_each_record() {
+        let body = "{\"id\":1}\n{\"id\":2}\n{\"id\":3}\n";
+        let response = respond(Reply::ok(body)).await;
+
+        let records: Vec<Record> = response
+            .json_lines::<Record>()
+            .map(|item| item.unwrap())
+            .collect()
+            .await;
+        assert_eq!(
+            records,
+            [Record { id: 1 }, Record { id: 2 }, Record { id: 3 }]
+        );
+    }
+
+    #[cfg(feature = "stream")]
+    #[tokio::test]
+    async fn json_lines_handles_split_chunks_blank_lines_and_no_trailing_newline() {
+        let chunks: &[&[u8]] = &[b"{\"id\":1}\r\n\n{\"i", b"d\":2}\n", b"  \n{\"id\":3}"];
+        let response = respond(Reply::chunked(200, &[], chunks)).await;
+
+        let records: Vec<Record> = response
+            .json_lines::<Record>()
+            .map(|item| item.unwrap())
+            .collect()
+            .await;
+        assert_eq!(
+            records,
+            [Record { id: 1 }, Record { id: 2 }, Record { id: 3 }]
+        );
+    }
+
+    #[cfg(feature = "stream")]
+    #[tokio::test]
+    async fn json_lines_reports_a_malformed_line_and_continues() {
+        let response = respond(Reply::ok("{\"id\":1}\nnot json\n{\"id\":3}\n")).await;
+
+        let items: Vec<Result<Record>> = response.json_lines::<Record>().collect().await;
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[0].as_ref().unwrap(), &Record { id: 1 });
+        assert!(matches!(items[1], Err(CallixError::JsonError(_))));
+        assert_eq!(items[2].as_ref().unwrap(), &Record { id: 3 });
+    }
+
+    #[cfg(feature = "stream")]
+    #[tokio::test]
+    async fn json_stream_array_yields_each_element() {
+        let chunks: &[&[u8]] = &[b" [{\"id\":1}, {\"i", b"d\":2},", b"{\"id\":3} ] "];
+        let response = respond(Reply::chunked(200, &[], chunks)).await;
+
+        let records: Vec<Record> = response
+            .json_stream_array::<Record>()
+            .map(|item| item.unwrap())
+            .collect()
+            .await;
+        assert_eq!(
+            records,
+            [Record { id: 1 }, Record { id: 2 }, Record { id: 3 }]
+        );
+    }
+
+    #[cfg(feature = "stream")]
+    #[tokio::test]
+    async fn json_stream_array_handles_nesting_and_strings() {
+        let body = r#"[{"a":[1,{"b":"],\"["}]}, "x,y", 3, []]"#;
+        let response = respond(Reply::ok(body)).await;
+
+        let items: Vec<Value> = response
+            .json_stream_array::<Value>()
+            .map(|item| item.unwrap())
+            .collect()
+            .await;
+        assert_eq!(
+            items,
+            [
+                serde_json::json!({"a": [1, {"b": "],\"["}]}),
+                serde_json::json!("x,y"),
+                serde_json::json!(3),
+                serde_json::json!([]),
+            ]
+        );
+
+        let response = respond(Reply::ok(" [ ] ")).await;
+        let items: Vec<Result<Value>> = response.json_stream_array().collect().await;
+        assert!(items.is_empty());
+    }
+
+    #[cfg(feature = "stream")]
+    #[tokio::test]
+    async fn json_stream_array_reports_malformed_bodies() {
+        let response = respond(Reply::ok(r#"{"id":1}"#)).await;
+        let items: Vec<Result<Value>> = response.json_stream_array().collect().await;
+        assert!(matches!(items[..], [Err(CallixError::InvalidBody(_))]));
+
+        let response = respond(Reply::ok(r#"[{"id":1},{"id""#)).await;
+        let items: Vec<Result<Record>> = response.json_stream_array().collect().await;
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].as_ref().unwrap(), &Record { id: 1 });
+        assert!(matches!(items[1], Err(CallixError::InvalidBody(_))));
+    }
+
+    #[tokio::test]
+    async fn error_body_truncates_oversized_bodies() {
+        let body = "e".repeat(ERROR_BODY_LIMIT + 100);
+        let response = respond(Reply::new(500, &[], &body)).await;
+
+        match response.error_for_status().await {
+            Err(CallixError::ApiError { status, body }) => {
+                assert_eq!(status, 500);
+                assert_eq!(body.len(), ERROR_BODY_LIMIT + TRUNCATED_MARKER.len());
+                assert!(body.ends_with(TRUNCATED_MARKER));
+            }
+            other => panic!("expected an API error, got {:?}", other.err()),
+        }
+    }
+
+    #[tokio::test]
+    async fn error_body_decodes_binary_bodies_lossily() {
+        let response = respond(Reply::new(502, &[], b"bad \xff gateway")).await;
+        assert_eq!(response.error_body().await, "bad \u{fffd} gateway");
     }
 }
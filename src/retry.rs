@@ -0,0 +1,244 @@
+use crate::error::CallixError;
+use crate::sleeper::{Sleeper, TokioSleeper};
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const RATE_LIMIT_RESET_HEADER: &str = "x-ratelimit-reset";
+const EPOCH_THRESHOLD: u64 = 1_000_000_000;
+const MAX_RATE_LIMIT_DELAY: Duration = Duration::from_secs(600);
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+pub(crate) type DelayFn = Arc<dyn Fn(u32, Option<&CallixError>) -> Duration + Send + Sync>;
+
+#[derive(Clone)]
+pub(crate) struct RetryPolicy {
+    pub(crate) max_retries: u32,
+    pub(crate) retry_delay: Duration,
+    pub(crate) max_attempts: Option<u32>,
+    pub(crate) max_delay: Option<Duration>,
+    pub(crate) retry_unsafe_methods: bool,
+    pub(crate) retry_statuses: Vec<u16>,
+    pub(crate) delay_fn: Option<DelayFn>,
+    pub(crate) total_deadline: Option<Duration>,
+    pub(crate) sleeper: Arc<dyn Sleeper>,
+}
+
+impl RetryPolicy {
+    pub(crate) fn new(max_retries: u32, retry_delay: Duration) -> Self {
+        Self {
+            max_retries,
+            retry_delay,
+            max_attempts: None,
+            max_delay: None,
+            retry_unsafe_methods: false,
+            retry_statuses: Vec::new(),
+            delay_fn: None,
+            total_deadline: None,
+            sleeper: Arc::new(TokioSleeper),
+        }
+    }
+
+    pub(crate) fn attempts(&self) -> u32 {
+        let attempts = self.max_retries.saturating_add(1);
+        match self.max_attempts {
+            Some(cap) => attempts.min(cap.max(1)),
+            None => attempts,
+        }
+    }
+
+    pub(crate) fn retries_status(&self, status: u16) -> bool {
+        self.retry_statuses.contains(&status)
+    }
+
+    pub(crate) fn delay_for(&self, attempt: u32, error: Option<&CallixError>) -> Duration {
+        let delay = match &self.delay_fn {
+            Some(delay_fn) => delay_fn(attempt, error),
+            None => self.retry_delay,
+        };
+        self.clamp_delay(delay)
+    }
+
+    pub(crate) fn clamp_delay(&self, delay: Duration) -> Duration {
+        match self.max_delay {
+            Some(max_delay) => delay.min(max_delay),
+            None => delay,
+        }
+    }
+}
+
+pub(crate) fn rate_limit_delay(headers: &HeaderMap) -> Option<Duration> {
+    let seconds = match header_secs(headers, RETRY_AFTER.as_str()) {
+        Some(seconds) => seconds,
+        None => match retry_after_date(headers) {
+            Some(date) => date.saturating_sub(now_secs()?),
+            None => {
+                let reset = header_secs(headers, RATE_LIMIT_RESET_HEADER)?;
+                if reset < EPOCH_THRESHOLD {
+                    reset
+                } else {
+                    reset.saturating_sub(now_secs()?)
+                }
+            }
+        },
+    };
+    Some(Duration::from_secs(seconds).min(MAX_RATE_LIMIT_DELAY))
+}
+
+fn header_secs(headers: &HeaderMap, name: &str) -> Option<u64> {
+    headers.get(name)?.to_str().ok()?.trim().parse().ok()
+}
+
+fn now_secs() -> Option<u64> {
+    Some(SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs())
+}
+
+// Only the IMF-fixdate form (`Sun, 06 Nov 1994 08:49:37 GMT`) that HTTP/1.1
+// senders must use is understood; the obsolete RFC 850 and asctime forms are not.
+fn retry_after_date(headers: &HeaderMap) -> Option<u64> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    let (_, date) = value.split_once(", ")?;
+    let fields: Vec<&str> = date.split(' ').collect();
+    let [day, month, year, time, "GMT"] = fields[..] else {
+        return None;
+    };
+
+    let month = MONTHS.iter().position(|name| *name == month)? as u64 + 1;
+    let (day, year) = (day.parse().ok()?, year.parse().ok()?);
+    let clock: Vec<u64> = time
+        .split(':')
+        .map(|field| field.parse().ok())
+        .collect::<Option<_>>()?;
+    let [hour, minute, second] = clock[..] else {
+        return None;
+    };
+    if hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    unix_seconds(year, month, day, hour * 3_600 + minute * 60 + second)
+}
+
+pub(crate) fn unix_seconds(year: u64, month: u64, day: u64, seconds_of_day: u64) -> Option<u64> {
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || year < 1970 {
+        return None;
+    }
+
+    let (y, m) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let era = y / 400;
+    let yoe = y % 400;
+    let doy = (153 * m + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+
+    Some(days * 86_400 + seconds_of_day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn max_attempts_caps_the_retry_count() {
+        let mut policy = RetryPolicy::new(10, Duration::ZERO);
+        assert_eq!(policy.attempts(), 11);
+
+        policy.max_attempts = Some(3);
+        assert_eq!(policy.attempts(), 3);
+
+        policy.max_attempts = Some(0);
+        assert_eq!(policy.attempts(), 1);
+
+        let mut policy = RetryPolicy::new(1, Duration::ZERO);
+        policy.max_attempts = Some(5);
+        assert_eq!(policy.attempts(), 2);
+    }
+
+    #[test]
+    fn max_delay_clamps_fixed_and_custom_delays() {
+        let mut policy = RetryPolicy::new(3, Duration::from_secs(30));
+        policy.max_delay = Some(Duration::from_secs(5));
+        assert_eq!(policy.delay_for(1, None), Duration::from_secs(5));
+
+        policy.delay_fn = Some(Arc::new(|attempt, _| {
+            Duration::from_secs(u64::from(attempt))
+        }));
+        assert_eq!(policy.delay_for(2, None), Duration::from_secs(2));
+        assert_eq!(policy.delay_for(9, None), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn rate_limit_reset_seconds_are_used_as_the_delay() {
+        let delay = rate_limit_delay(&headers(&[("x-ratelimit-reset", "7")]));
+        assert_eq!(delay, Some(Duration::from_secs(7)));
+    }
+
+    #[test]
+    fn rate_limit_reset_epochs_count_down_from_now() {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        let reset = (now + 30).to_string();
+        let delay = rate_limit_delay(&headers(&[("x-ratelimit-reset", &reset)])).unwrap();
+        assert!((28..=30).contains(&delay.as_secs()), "delay {:?}", delay);
+
+        let past = (now - 30).to_string();
+        let delay = rate_limit_delay(&headers(&[("x-ratelimit-reset", &past)]));
+        assert_eq!(delay, Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn far_future_resets_are_capped() {
+        let delay = rate_limit_delay(&headers(&[("x-ratelimit-reset", "99999999999")]));
+        assert_eq!(delay, Some(MAX_RATE_LIMIT_DELAY));
+
+        let delay = rate_limit_delay(&headers(&[("retry-after", "86400")]));
+        assert_eq!(delay, Some(MAX_RATE_LIMIT_DELAY));
+    }
+
+    #[test]
+    fn retry_after_accepts_http_dates() {
+        let date = headers(&[("retry-after", "Sun, 06 Nov 1994 08:49:37 GMT")]);
+        assert_eq!(retry_after_date(&date), Some(784_111_777));
+        assert_eq!(rate_limit_delay(&date), Some(Duration::ZERO));
+
+        let future = headers(&[("retry-after", "Fri, 31 Dec 9999 23:59:59 GMT")]);
+        assert_eq!(rate_limit_delay(&future), Some(MAX_RATE_LIMIT_DELAY));
+
+        for malformed in [
+            "Sunday, 06-Nov-94 08:49:37 GMT",
+            "Sun Nov  6 08:49:37 1994",
+            "Sun, 06 Nov 1994 25:49:37 GMT",
+            "Sun, 06 Foo 1994 08:49:37 GMT",
+        ] {
+            let malformed = headers(&[("retry-after", malformed)]);
+            assert_eq!(rate_limit_delay(&malformed), None);
+        }
+    }
+
+    #[test]
+    fn missing_or_malformed_rate_limit_headers_give_no_delay() {
+        assert_eq!(rate_limit_delay(&HeaderMap::new()), None);
+        assert_eq!(
+            rate_limit_delay(&headers(&[("x-ratelimit-reset", "soon")])),
+            None
+        );
+    }
+}
@@ -0,0 +1,38 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+
+type Extractor = Box<dyn Fn(&Value) -> HashMap<String, Value> + Send + Sync>;
+
+pub struct Step {
+    pub(crate) provider: String,
+    pub(crate) endpoint: String,
+    pub(crate) variables: HashMap<String, Value>,
+    pub(crate) extractor: Option<Extractor>,
+}
+
+impl Step {
+    pub fn new(provider: impl Into<String>, endpoint: impl Into<String>) -> Self {
+        Self {
+            provider: provider.into(),
+            endpoint: endpoint.into(),
+            variables: HashMap::new(),
+            extractor: None,
+        }
+    }
+
+    pub fn var<T: Serialize>(mut self, key: impl Into<String>, value: T) -> Self {
+        if let Ok(json_value) = serde_json::to_value(value) {
+            self.variables.insert(key.into(), json_value);
+        }
+        self
+    }
+
+    pub fn extract(
+        mut self,
+        extractor: impl Fn(&Value) -> HashMap<String, Value> + Send + Sync + 'static,
+    ) -> Self {
+        self.extractor = Some(Box::new(extractor));
+        self
+    }
+}
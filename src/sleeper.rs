@@ -0,0 +1,50 @@
+use crate::middleware::BoxFuture;
+use std::time::Duration;
+
+pub trait Sleeper: Send + Sync {
+    fn sleep(&self, duration: Duration) -> BoxFuture<'_, ()>;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TokioSleeper;
+
+impl Sleeper for TokioSleeper {
+    fn sleep(&self, duration: Duration) -> BoxFuture<'_, ()> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CallixBuilder;
+    use crate::config::EndpointConfig;
+    use crate::test_support::{MockServer, RecordingSleeper, Reply, mock_client};
+    use std::time::Instant;
+
+    #[tokio::test]
+    async fn an_injected_sleeper_sees_the_delay_sequence_without_waiting() {
+        let server = MockServer::start(vec![Reply::new(503, &[], "busy")]).await;
+        let sleeper = RecordingSleeper::default();
+        let builder = CallixBuilder::new()
+            .retries(3)
+            .retry_on_status(&[503])
+            .retry_delay_fn(|attempt, _| Duration::from_secs(u64::from(attempt) * 60))
+            .sleeper(sleeper.clone());
+        let callix = mock_client(builder, &server, EndpointConfig::new("GET", "/"));
+
+        let started = Instant::now();
+        let response = callix.request("mock", "call").unwrap().send().await;
+        assert_eq!(response.unwrap().status(), 503);
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(sleeper.delays(), [60, 120, 180].map(Duration::from_secs));
+        assert_eq!(server.hits(), 4);
+    }
+
+    #[tokio::test]
+    async fn tokio_sleeper_waits_for_the_duration() {
+        let started = Instant::now();
+        TokioSleeper.sleep(Duration::from_millis(20)).await;
+        assert!(started.elapsed() >= Duration::from_millis(20));
+    }
+}
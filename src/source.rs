@@ -0,0 +1,12 @@
+use serde_json::Value;
+use std::collections::HashMap;
+
+pub trait VariableSource: Send + Sync {
+    fn get(&self, key: &str) -> Option<Value>;
+}
+
+impl VariableSource for HashMap<String, Value> {
+    fn get(&self, key: &str) -> Option<Value> {
+        HashMap::get(self, key).cloned()
+    }
+}
@@ -1,14 +1,87 @@
 use crate::error::{CallixError, Result};
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, utf8_percent_encode};
 use serde_json::Value;
 use std::borrow::Cow;
 use std::collections::HashMap;
 
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RenderOptions {
+    pub sort_keys: bool,
+    pub encode_path: bool,
+}
+
+const PATH_VALUE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~')
+    .remove(b':')
+    .remove(b'@')
+    .remove(b'!')
+    .remove(b'$')
+    .remove(b'&')
+    .remove(b'\'')
+    .remove(b'(')
+    .remove(b')')
+    .remove(b'*')
+    .remove(b'+')
+    .remove(b',')
+    .remove(b';')
+    .remove(b'=');
+
+pub(crate) fn encode_path_value(value: &str) -> String {
+    value
+        .split('/')
+        .map(|piece| match piece {
+            "." => "%2E".to_string(),
+            ".." => "%2E%2E".to_string(),
+            piece => utf8_percent_encode(piece, PATH_VALUE).to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("%2F")
+}
+
+pub type TemplateTrace = Vec<(String, String)>;
+
 pub struct TemplateEngine;
 
 impl TemplateEngine {
+    #[inline]
     pub fn render<'a>(
         template: &'a str,
         variables: &HashMap<String, Value>,
+    ) -> Result<Cow<'a, str>> {
+        Self::render_with(template, variables, RenderOptions::default())
+    }
+
+    #[inline]
+    pub fn render_with<'a>(
+        template: &'a str,
+        variables: &HashMap<String, Value>,
+        options: RenderOptions,
+    ) -> Result<Cow<'a, str>> {
+        Self::render_inner(template, variables, options, None)
+    }
+
+    pub fn render_traced<'a>(
+        template: &'a str,
+        variables: &HashMap<String, Value>,
+    ) -> Result<(Cow<'a, str>, TemplateTrace)> {
+        let mut trace = TemplateTrace::new();
+        let rendered = Self::render_inner(
+            template,
+            variables,
+            RenderOptions::default(),
+            Some(&mut trace),
+        )?;
+        Ok((rendered, trace))
+    }
+
+    fn render_inner<'a>(
+        template: &'a str,
+        variables: &HashMap<String, Value>,
+        options: RenderOptions,
+        mut trace: Option<&mut TemplateTrace>,
     ) -> Result<Cow<'a, str>> {
         if !template.contains("{{") {
             return Ok(Cow::Borrowed(template));
@@ -20,17 +93,47 @@ impl TemplateEngine {
 
         while let Some(c) = chars.next() {
             if c == '{' {
-                if let Some('{') = chars.next() {
+                let next = chars.next();
+                if let Some('{') = next {
                     buffer.clear();
                     let iter = chars.by_ref();
                     while let Some(c) = iter.next() {
                         if c == '}' {
                             if let Some('}') = iter.next() {
-                                let var_name = buffer.trim();
-                                if let Some(value) = variables.get(var_name) {
-                                    result.push_str(&Self::value_to_string(value)?);
-                                } else {
+                                let expression = buffer.trim();
+                                let (var_name, optional) = Self::parse_expression(expression);
+                                let json = match Self::split_filter(expression).1 {
+                                    None => false,
+                                    Some("json") => true,
+                                    Some(_) => return Err(CallixError::TemplateError),
+                                };
+                                if let Some(value) = Self::lookup(variables, var_name) {
+                                    let rendered = if json {
+                                        Self::value_to_json(value, options)?
+                                    } else {
+                                        Self::value_to_string(value, options)?
+                                    };
+                                    if let Some(trace) = trace.as_mut() {
+                                        trace.push((var_name.to_string(), rendered.clone()));
+                                    }
+                                    if options.encode_path {
+                                        if matches!(rendered.as_str(), "." | "..") {
+                                            return Err(CallixError::InvalidVariable {
+                                                name: var_name.to_string(),
+                                                reason: format!(
+                                                    "'{}' is not allowed as a path segment",
+                                                    rendered
+                                                ),
+                                            });
+                                        }
+                                        result.push_str(&encode_path_value(&rendered));
+                                    } else {
+                                        result.push_str(&rendered);
+                                    }
+                                } else if !optional {
                                     return Err(CallixError::TemplateError);
+                                } else if json {
+                                    result.push_str("null");
                                 }
                                 break;
                             }
@@ -40,6 +143,7 @@ impl TemplateEngine {
                     }
                 } else {
                     result.push(c);
+                    result.extend(next);
                 }
             } else {
                 result.push(c);
@@ -49,15 +153,366 @@ impl TemplateEngine {
         Ok(Cow::Owned(result))
     }
 
-    fn value_to_string(value: &Value) -> Result<String> {
+    #[inline]
+    pub fn render_value(template: &Value, variables: &HashMap<String, Value>) -> Result<Value> {
+        Self::render_value_with(template, variables, RenderOptions::default())
+    }
+
+    pub fn render_value_with(
+        template: &Value,
+        variables: &HashMap<String, Value>,
+        options: RenderOptions,
+    ) -> Result<Value> {
+        Ok(Self::render_value_inner(template, variables, options)?.unwrap_or(Value::Null))
+    }
+
+    fn render_value_inner(
+        template: &Value,
+        variables: &HashMap<String, Value>,
+        options: RenderOptions,
+    ) -> Result<Option<Value>> {
+        match template {
+            Value::String(s) => match Self::single_variable(s) {
+                Some(expression) => {
+                    let (name, optional) = Self::parse_expression(expression);
+                    match Self::lookup(variables, name) {
+                        Some(value) => Ok(Some(value.clone())),
+                        None if optional => Ok(None),
+                        None => Err(CallixError::TemplateError),
+                    }
+                }
+                None => Ok(Some(Value::String(
+                    Self::render_with(s, variables, options)?.into_owned(),
+                ))),
+            },
+            Value::Array(items) => {
+                let mut rendered = Vec::with_capacity(items.len());
+                for item in items {
+                    rendered.extend(Self::render_value_inner(item, variables, options)?);
+                }
+                Ok(Some(Value::Array(rendered)))
+            }
+            Value::Object(map) => {
+                let mut rendered = serde_json::Map::with_capacity(map.len());
+                for (key, value) in map {
+                    if let Some(value) = Self::render_value_inner(value, variables, options)? {
+                        rendered.insert(key.clone(), value);
+                    }
+                }
+                Ok(Some(Value::Object(rendered)))
+            }
+            other => Ok(Some(other.clone())),
+        }
+    }
+
+    fn parse_expression(expression: &str) -> (&str, bool) {
+        let (expression, _) = Self::split_filter(expression);
+        match expression.strip_suffix('?') {
+            Some(name) => (name.trim_end(), true),
+            None => (expression, false),
+        }
+    }
+
+    fn split_filter(expression: &str) -> (&str, Option<&str>) {
+        match expression.split_once('|') {
+            Some((expression, filter)) => (expression.trim_end(), Some(filter.trim())),
+            None => (expression, None),
+        }
+    }
+
+    pub fn lookup<'v>(variables: &'v HashMap<String, Value>, name: &str) -> Option<&'v Value> {
+        if let Some(value) = variables.get(name) {
+            return Some(value);
+        }
+
+        let (namespace, path) = name.split_once('.')?;
+        path.split('.')
+            .try_fold(variables.get(namespace)?, |value, segment| {
+                match segment.parse::<usize>() {
+                    Ok(index) if value.is_array() => value.get(index),
+                    _ => value.get(segment),
+                }
+            })
+    }
+
+    pub(crate) fn single_variable(template: &str) -> Option<&str> {
+        let inner = template.trim().strip_prefix("{{")?.strip_suffix("}}")?;
+
+        if inner.contains("{{") || inner.contains("}}") {
+            return None;
+        }
+        Some(inner.trim())
+    }
+
+    pub(crate) fn render_list(
+        template: &str,
+        variables: &HashMap<String, Value>,
+        options: RenderOptions,
+    ) -> Option<Result<Vec<String>>> {
+        let (name, _) = Self::parse_expression(Self::single_variable(template)?);
+        let Value::Array(items) = Self::lookup(variables, name)? else {
+            return None;
+        };
+        Some(
+            items
+                .iter()
+                .map(|item| Self::value_to_string(item, options))
+                .collect(),
+        )
+    }
+
+    pub fn extract_value_variables(template: &Value) -> Vec<String> {
+        let mut variables = Vec::new();
+        Self::collect_value_variables(template, &mut variables);
+        variables
+    }
+
+    fn collect_value_variables(template: &Value, variables: &mut Vec<String>) {
+        match template {
+            Value::String(s) => {
+                for name in Self::extract_variables(s) {
+                    if !variables.contains(&name) {
+                        variables.push(name);
+                    }
+                }
+            }
+            Value::Array(items) => {
+                for item in items {
+                    Self::collect_value_variables(item, variables);
+                }
+            }
+            Value::Object(map) => {
+                for value in map.values() {
+                    Self::collect_value_variables(value, variables);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn extract_variables(template: &str) -> Vec<String> {
+        let mut variables = Vec::new();
+        let mut rest = template;
+
+        while let Some(start) = rest.find("{{") {
+            let after = &rest[start + 2..];
+            let Some(end) = after.find("}}") else {
+                break;
+            };
+
+            let (name, _) = Self::parse_expression(after[..end].trim());
+            if !variables.iter().any(|v| v == name) {
+                variables.push(name.to_string());
+            }
+            rest = &after[end + 2..];
+        }
+
+        variables
+    }
+
+    pub fn sort_keys(value: &Value) -> Value {
+        match value {
+            Value::Object(map) => {
+                let mut entries: Vec<_> = map.iter().collect();
+                entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+                Value::Object(
+                    entries
+                        .into_iter()
+                        .map(|(k, v)| (k.clone(), Self::sort_keys(v)))
+                        .collect(),
+                )
+            }
+            Value::Array(items) => Value::Array(items.iter().map(Self::sort_keys).collect()),
+            other => other.clone(),
+        }
+    }
+
+    fn value_to_json(value: &Value, options: RenderOptions) -> Result<String> {
+        let result = if options.sort_keys {
+            serde_json::to_string(&Self::sort_keys(value))
+        } else {
+            serde_json::to_string(value)
+        };
+        result.map_err(|_| CallixError::TemplateError)
+    }
+
+    fn value_to_string(value: &Value, options: RenderOptions) -> Result<String> {
         match value {
             Value::String(s) => Ok(s.clone()),
             Value::Number(n) => Ok(n.to_string()),
             Value::Bool(b) => Ok(b.to_string()),
             Value::Null => Ok(String::from("null")),
+            Value::Array(_) | Value::Object(_) if options.sort_keys => {
+                serde_json::to_string(&Self::sort_keys(value))
+                    .map_err(|_| CallixError::TemplateError)
+            }
             Value::Array(_) | Value::Object(_) => {
                 serde_json::to_string(value).map_err(|_| CallixError::TemplateError)
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn vars(value: Value) -> HashMap<String, Value> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn keeps_the_character_after_a_lone_brace() {
+        let variables = vars(json!({"value": 1}));
+
+        let rendered = TemplateEngine::render(r#"{"a": {{value}}}"#, &variables).unwrap();
+        assert_eq!(rendered, r#"{"a": 1}"#);
+
+        let rendered = TemplateEngine::render(r#"{"a":{"b":"{{value}}"}}"#, &variables).unwrap();
+        assert_eq!(rendered, r#"{"a":{"b":"1"}}"#);
+    }
+
+    #[test]
+    fn keeps_a_trailing_lone_brace() {
+        let variables = vars(json!({"value": "x"}));
+
+        let rendered = TemplateEngine::render("{{value}} {", &variables).unwrap();
+        assert_eq!(rendered, "x {");
+    }
+
+    #[test]
+    fn extracts_variables_in_order_without_duplicates() {
+        let variables =
+            TemplateEngine::extract_variables("/{{ model }}/{{id}}?k={{model}}&v={{version}}");
+        assert_eq!(variables, ["model", "id", "version"]);
+    }
+
+    #[test]
+    fn extract_variables_ignores_an_unclosed_placeholder() {
+        assert_eq!(TemplateEngine::extract_variables("{{a}} {{b"), ["a"]);
+    }
+
+    #[test]
+    fn sort_keys_renders_nested_objects_in_key_order() {
+        let variables = vars(json!({
+            "payload": {"b": 1, "a": {"d": [{"z": 1, "y": 2}], "c": 3}}
+        }));
+        let options = RenderOptions {
+            sort_keys: true,
+            ..RenderOptions::default()
+        };
+
+        let first = TemplateEngine::render_with("{{payload}}", &variables, options).unwrap();
+        let second = TemplateEngine::render_with("{{payload}}", &variables, options).unwrap();
+        assert_eq!(first, r#"{"a":{"c":3,"d":[{"y":2,"z":1}]},"b":1}"#);
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn render_value_keeps_variable_types() {
+        let variables = vars(json!({
+            "temperature": 0.7,
+            "stream": true,
+            "messages": [{"role": "user"}],
+            "model": "m-1"
+        }));
+        let template = json!({
+            "temperature": "{{temperature}}",
+            "stream": "{{ stream }}",
+            "messages": "{{messages}}",
+            "label": "model {{model}}",
+            "fixed": 3
+        });
+
+        let rendered = TemplateEngine::render_value(&template, &variables).unwrap();
+        assert_eq!(
+            rendered,
+            json!({
+                "temperature": 0.7,
+                "stream": true,
+                "messages": [{"role": "user"}],
+                "label": "model m-1",
+                "fixed": 3
+            })
+        );
+    }
+
+    #[test]
+    fn render_value_rejects_missing_variables() {
+        let template = json!({"temperature": "{{temperature}}"});
+        assert!(matches!(
+            TemplateEngine::render_value(&template, &HashMap::new()),
+            Err(CallixError::TemplateError)
+        ));
+    }
+
+    #[test]
+    fn render_traced_records_each_resolution() {
+        let variables = vars(json!({"model": "m-1", "max_tokens": 64}));
+
+        let (rendered, trace) = TemplateEngine::render_traced(
+            r#"{"model": "{{model}}", "max_tokens": {{ max_tokens }}}"#,
+            &variables,
+        )
+        .unwrap();
+        assert_eq!(rendered, r#"{"model": "m-1", "max_tokens": 64}"#);
+        assert_eq!(
+            trace,
+            [
+                ("model".to_string(), "m-1".to_string()),
+                ("max_tokens".to_string(), "64".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn render_value_drops_unset_optional_fields() {
+        let variables = vars(json!({"model": "m-1", "temperature": 0.2}));
+        let template = json!({
+            "model": "{{model}}",
+            "max_tokens": "{{max_tokens?}}",
+            "temperature": "{{temperature?}}",
+            "stop": ["{{stop?}}", "END"]
+        });
+
+        let rendered = TemplateEngine::render_value(&template, &variables).unwrap();
+        assert_eq!(
+            rendered,
+            json!({"model": "m-1", "temperature": 0.2, "stop": ["END"]})
+        );
+    }
+
+    #[test]
+    fn json_filter_escapes_strings_into_valid_json() {
+        let variables = vars(json!({"message": "say \"hi\"\nthen\\leave"}));
+
+        let rendered =
+            TemplateEngine::render(r#"{"content": {{ message | json }}}"#, &variables).unwrap();
+        assert_eq!(rendered, r#"{"content": "say \"hi\"\nthen\\leave"}"#);
+        let parsed: Value = serde_json::from_str(&rendered).unwrap();
+        assert_eq!(parsed["content"], "say \"hi\"\nthen\\leave");
+    }
+
+    #[test]
+    fn json_filter_serializes_non_strings_and_unset_optionals() {
+        let variables = vars(json!({"count": 3, "tags": ["a", "b"]}));
+
+        let rendered = TemplateEngine::render(
+            r#"[{{count | json}}, {{tags | json}}, {{missing? | json}}]"#,
+            &variables,
+        )
+        .unwrap();
+        assert_eq!(rendered, r#"[3, ["a","b"], null]"#);
+    }
+
+    #[test]
+    fn unknown_filters_are_template_errors() {
+        let variables = vars(json!({"message": "hi"}));
+
+        assert!(matches!(
+            TemplateEngine::render("{{message | upper}}", &variables),
+            Err(CallixError::TemplateError)
+        ));
+    }
+}
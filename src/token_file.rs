@@ -0,0 +1,103 @@
+use crate::error::Result;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use std::time::SystemTime;
+
+#[derive(Default)]
+pub(crate) struct TokenFiles {
+    cache: Mutex<HashMap<PathBuf, (SystemTime, String)>>,
+}
+
+impl TokenFiles {
+    pub(crate) fn read(&self, path: &Path) -> Result<String> {
+        let modified = fs::metadata(path)?.modified()?;
+        let mut cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some((cached_at, token)) = cache.get(path)
+            && *cached_at == modified
+        {
+            return Ok(token.clone());
+        }
+
+        let token = read_token(path)?;
+        cache.insert(path.to_path_buf(), (modified, token.clone()));
+        Ok(token)
+    }
+}
+
+pub(crate) fn read_token(path: &Path) -> Result<String> {
+    Ok(fs::read_to_string(path)?.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CallixBuilder;
+    use crate::config::{AuthConfig, EndpointConfig, ProviderConfig};
+    use crate::error::CallixError;
+    use crate::test_support::TempFile;
+    use std::time::Duration;
+
+    fn rewrite(file: &TempFile, token: &str, modified: SystemTime) {
+        fs::write(file.path(), token).unwrap();
+        let handle = fs::File::options().write(true).open(file.path()).unwrap();
+        handle.set_modified(modified).unwrap();
+    }
+
+    fn modified(file: &TempFile) -> SystemTime {
+        fs::metadata(file.path()).unwrap().modified().unwrap()
+    }
+
+    #[test]
+    fn read_trims_the_token() {
+        let file = TempFile::new("token", "  tok-1\n");
+        assert_eq!(TokenFiles::default().read(file.path()).unwrap(), "tok-1");
+    }
+
+    #[test]
+    fn unchanged_files_are_served_from_the_cache() {
+        let file = TempFile::new("token", "tok-1");
+        let tokens = TokenFiles::default();
+        let original = modified(&file);
+        assert_eq!(tokens.read(file.path()).unwrap(), "tok-1");
+
+        rewrite(&file, "tok-2", original);
+        assert_eq!(tokens.read(file.path()).unwrap(), "tok-1");
+
+        rewrite(&file, "tok-2", original + Duration::from_secs(1));
+        assert_eq!(tokens.read(file.path()).unwrap(), "tok-2");
+    }
+
+    #[test]
+    fn missing_files_are_io_errors() {
+        let path = std::env::temp_dir().join("callix-test-missing.token");
+        assert!(matches!(
+            TokenFiles::default().read(&path),
+            Err(CallixError::IoError(_))
+        ));
+    }
+
+    #[test]
+    fn rotated_tokens_reach_the_authorization_header() {
+        let file = TempFile::new("token", "tok-1\n");
+        let mut callix = CallixBuilder::new().build().unwrap();
+        let provider = ProviderConfig {
+            auth: Some(AuthConfig::BearerFile {
+                path: file.path_str().to_string(),
+            }),
+            ..ProviderConfig::new("https://api.test")
+        }
+        .endpoint("ping", EndpointConfig::new("GET", "/ping"));
+        callix.register_provider("k8s", provider).unwrap();
+
+        let authorization = || {
+            let request = callix.request("k8s", "ping").unwrap();
+            request.build_request().unwrap().headers["authorization"].clone()
+        };
+        assert_eq!(authorization(), "Bearer tok-1");
+
+        rewrite(&file, "tok-2\n", modified(&file) + Duration::from_secs(1));
+        assert_eq!(authorization(), "Bearer tok-2");
+    }
+}